@@ -3,7 +3,7 @@ use tui::{
     layout::{Constraint, Layout, Rect},
     style::{Color, Style},
     text::{Span, Spans},
-    widgets::{Block, Borders, Tabs},
+    widgets::{Block, Borders, Clear, Tabs},
     Frame,
 };
 
@@ -13,30 +13,281 @@ use crate::{
     tabs::{
         incread::logic::MainInc,
         review::logic::MainReview,
+        tools::logic::Tools,
         //browse::logic::Browse,
     },
-    utils::misc::split_leftright,
-    widgets::textinput::Field,
+    utils::misc::{centered_rect, split_leftright, PopUpStatus},
+    widgets::{
+        message_box::draw_message,
+        search::{SearchWidget, Target},
+        textinput::Field,
+    },
     MyType, SpekiPaths,
 };
 
 
 
 use serde_derive::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::utils::aliases::TopicID;
+use crate::utils::order::ReviewOrder;
+use crate::widgets::topics::Topic;
+use crate::utils::scheduler::{
+    classic::ClassicParams, fsrs::FsrsParams, new_scheduler, Scheduler, SchedulerKind,
+};
 
 
 #[derive(Deserialize)]
 pub struct Config{
     pub gptkey: Option<String>,
+    #[serde(default)]
+    pub scheduler: SchedulerKind,
+    #[serde(default)]
+    pub classic: ClassicParams,
+    #[serde(default)]
+    pub fsrs: FsrsParams,
+    #[serde(default = "Config::default_retention")]
+    pub retention: f32,
+    #[serde(default)]
+    pub topics: HashMap<String, TopicConfig>,
+    #[serde(default)]
+    pub forecast: ForecastConfig,
+    #[serde(default)]
+    pub on_failed_dependency: FailedDependency,
+    #[serde(default = "Config::default_weakening")]
+    pub dependent_weakening: f32,
+    pub new_per_day: Option<u32>,
+    pub reviews_per_day: Option<u32>,
+    #[serde(default = "Config::default_leech_threshold")]
+    pub leech_threshold: u32,
+    #[serde(default)]
+    pub leech_action: LeechAction,
+    #[serde(default = "Config::default_bury_siblings")]
+    pub bury_siblings: bool,
+    #[serde(default = "Config::default_learning_steps")]
+    pub learning_steps: Vec<f32>,
+    #[serde(default)]
+    pub review_order: ReviewOrder,
+    #[serde(default = "Config::default_trash_retention")]
+    pub trash_retention_days: u32,
+    #[serde(default)]
+    pub startup_check: StartupCheck,
+    #[serde(default)]
+    pub backup: BackupConfig,
+}
+
+/// Whether the database gets checked for inconsistencies every time speki starts.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum StartupCheck{
+    #[default]
+    None,
+    /// Show what's wrong, but leave it.
+    Report,
+    Repair,
+}
+
+/// What happens to a card once it's flagged as a leech.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LeechAction{
+    /// Only tag it, so it shows up in the list of leeches.
+    #[default]
+    Tag,
+    Suspend,
+    /// Turn it into an unfinished card so it gets rewritten.
+    Unfinished,
+}
+
+/// What happens to the direct dependents of a card that gets failed during review.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FailedDependency{
+    #[default]
+    None,
+    /// Multiply their stability by `dependent_weakening`, so they come up sooner. It isn't
+    /// part of their history, so it only lasts until their next review or a recompute.
+    Weaken,
+    /// Add them to the current review session.
+    Requeue,
+}
+
+/// How far ahead the workload forecast looks and how many new cards a day it assumes.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ForecastConfig{
+    pub days: usize,
+    pub new_per_day: u32,
+}
+
+impl Default for ForecastConfig{
+    fn default() -> Self{
+        Self{
+            days: 30,
+            new_per_day: 10,
+        }
+    }
+}
+
+/// How many snapshots of the database to keep, the newest one of each of the last so many
+/// days, weeks and months.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct BackupConfig{
+    pub daily: usize,
+    pub weekly: usize,
+    pub monthly: usize,
+}
+
+impl Default for BackupConfig{
+    fn default() -> Self{
+        Self{
+            daily: 7,
+            weekly: 4,
+            monthly: 12,
+        }
+    }
+}
+
+/// Per-topic overrides, keyed by topic name in the config file. A setting on a topic
+/// also applies to all its subtopics unless they override it themselves.
+#[derive(Deserialize, Default)]
+pub struct TopicConfig{
+    pub retention: Option<f32>,
+    pub new_per_day: Option<u32>,
+    pub reviews_per_day: Option<u32>,
 }
 
 use toml;
 
 impl Config{
-    fn new(paths: &SpekiPaths) -> Self{
-        let contents = std::fs::read_to_string(&paths.config).expect("Error reading file");
-        let config: Config = toml::from_str(&contents).expect("invalid config file");
-        config
+    pub fn new(paths: &SpekiPaths) -> crate::error::Result<Self>{
+        let contents = std::fs::read_to_string(&paths.config)?;
+        let config: Config = toml::from_str(&contents)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Rejects settings that parse fine but make no sense. A retention of 0 or 1 and beyond
+    /// would give infinite or negative intervals.
+    fn validate(&self) -> crate::error::Result<()> {
+        let check_retention = |retention: f32, name: &str| {
+            if retention > 0. && retention < 1. {
+                Ok(())
+            } else {
+                Err(crate::error::SpekiError::Config(format!(
+                    "{} is {}, it has to be between 0 and 1",
+                    name, retention
+                )))
+            }
+        };
+        check_retention(self.retention, "retention")?;
+        for (topic, config) in &self.topics {
+            if let Some(retention) = config.retention {
+                check_retention(retention, &format!("retention of topic {}", topic))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// What a config file without any settings in it would give.
+    pub fn fallback() -> Self{
+        toml::from_str("").unwrap()
+    }
+
+    /// Stores parameters for the active scheduler, both here and in the config file.
+    pub fn save_parameters(&mut self, path: &Path, params: &[f32]) -> anyhow::Result<()> {
+        let (key, value) = match self.scheduler {
+            SchedulerKind::Classic => {
+                self.classic = ClassicParams::from_slice(params);
+                ("classic", toml::Value::try_from(&self.classic)?)
+            }
+            SchedulerKind::Fsrs => {
+                self.fsrs = FsrsParams::from_slice(params);
+                ("fsrs", toml::Value::try_from(&self.fsrs)?)
+            }
+        };
+        let contents = std::fs::read_to_string(path)?;
+        let mut doc: toml::Value = toml::from_str(&contents)?;
+        doc.as_table_mut()
+            .unwrap()
+            .insert(key.to_string(), value);
+        std::fs::write(path, toml::to_string(&doc)?)?;
+        Ok(())
+    }
+
+    pub fn scheduler(&self) -> Box<dyn Scheduler> {
+        new_scheduler(self.scheduler, &self.classic, &self.fsrs)
+    }
+
+    fn default_retention() -> f32 {
+        0.9
+    }
+
+    fn default_weakening() -> f32 {
+        0.5
+    }
+
+    fn default_leech_threshold() -> u32 {
+        8
+    }
+
+    fn default_bury_siblings() -> bool {
+        true
+    }
+
+    fn default_learning_steps() -> Vec<f32> {
+        vec![1., 10.]
+    }
+
+    fn default_trash_retention() -> u32 {
+        30
+    }
+
+    /// Walks up from the given topic until it finds one with the setting.
+    pub fn topic_setting<T, F>(&self, topics: &[Topic], id: TopicID, get: F) -> Option<T>
+    where
+        F: Fn(&TopicConfig) -> Option<T>,
+    {
+        let mut id = id;
+        while let Some(topic) = topics.iter().find(|topic| topic.id == id) {
+            if let Some(val) = self.topics.get(&topic.name).and_then(&get) {
+                return Some(val);
+            }
+            id = topic.parent;
+        }
+        None
+    }
+
+    pub fn retention(&self, topics: &[Topic], id: TopicID) -> f32 {
+        self.topic_setting(topics, id, |topic| topic.retention)
+            .unwrap_or(self.retention)
+    }
+
+    /// The topics that set a limit themselves, as opposed to inheriting it.
+    pub fn topic_limits<F>(&self, topics: &[Topic], get: F) -> Vec<(TopicID, u32)>
+    where
+        F: Fn(&TopicConfig) -> Option<u32>,
+    {
+        topics
+            .iter()
+            .filter_map(|topic| {
+                self.topics
+                    .get(&topic.name)
+                    .and_then(&get)
+                    .map(|limit| (topic.id, limit))
+            })
+            .collect()
+    }
+
+    /// The topics whose retention differs from the global one.
+    pub fn retention_overrides(&self, topics: &[Topic]) -> Vec<(TopicID, f32)> {
+        topics
+            .iter()
+            .map(|topic| (topic.id, self.retention(topics, topic.id)))
+            .filter(|(_, retention)| *retention != self.retention)
+            .collect()
     }
 }
 
@@ -47,7 +298,7 @@ pub struct Audio{
 
 impl Audio{
     fn new() -> Option<Self>{
-        let (source, handle) = rodio::OutputStream::try_default().unwrap();
+        let (source, handle) = rodio::OutputStream::try_default().ok()?;
         Some(Audio{
             source,
             handle
@@ -60,7 +311,12 @@ pub struct AppData{
     pub conn: Arc<Mutex<Connection>>,
     pub audio: Option<Audio>,
     pub paths: SpekiPaths,
-    pub config: Config
+    pub config: Config,
+    /// Set once the database got swapped out from under the tabs, like after restoring a
+    /// backup. The tabs get rebuilt and the message is shown.
+    pub reload: Option<String>,
+    /// Set to open another profile, which replaces the whole app.
+    pub switch_to: Option<SpekiPaths>,
 }
 
 pub struct TabsState {
@@ -72,21 +328,24 @@ impl TabsState {
     pub fn new(
         conn: &Arc<Mutex<Connection>>,
         audio: &Option<Audio>,
-    ) -> TabsState {
+        config: &Config,
+    ) -> crate::error::Result<TabsState> {
         let mut tabs: Vec<Box<dyn Tab>> = vec![];
-        let revlist  = MainReview::new(conn, audio);
-        let addcards = NewCard::new(conn, DepState::None);
-        let incread  = MainInc::new(conn);
+        let revlist  = MainReview::new(conn, audio, config)?;
+        let addcards = NewCard::new(conn, DepState::None)?;
+        let incread  = MainInc::new(conn)?;
         let importer = Importer::new(conn);
+        let tools    = Tools::new();
         //let browse = Browse::new();
 
         tabs.push(Box::new(revlist));
         tabs.push(Box::new(addcards));
         tabs.push(Box::new(incread));
         tabs.push(Box::new(importer));
+        tabs.push(Box::new(tools));
         //tabs.push(Box::new(browse));
 
-        TabsState { tabs, index: 0 }
+        Ok(TabsState { tabs, index: 0 })
     }
     pub fn next(&mut self) {
         if self.index < self.tabs.len() - 1 {
@@ -115,15 +374,35 @@ impl TabsState {
         self.next();
     }
 
-    fn keyhandler(&mut self, appdata: &AppData, key: MyKey) {
-        self.tabs[self.index].keyhandler(appdata, key);
+    fn keyhandler(&mut self, appdata: &mut AppData, key: MyKey) -> crate::error::Result<()> {
+        self.tabs[self.index].keyhandler(appdata, key)
     }
     fn render(&mut self, f: &mut Frame<MyType>, appdata: &AppData, area: Rect) {
         self.tabs[self.index].render(f, appdata, area);
     }
+    fn tick(&mut self, appdata: &mut AppData) -> crate::error::Result<()> {
+        for tab in &mut self.tabs {
+            tab.tick(appdata)?;
+        }
+        Ok(())
+    }
+
+    /// Switches to the first tab that can show the target.
+    fn jump_to(&mut self, appdata: &mut AppData, target: Target) -> crate::error::Result<()> {
+        for index in 0..self.tabs.len() {
+            if self.tabs[index].jump_to(appdata, target)? {
+                self.index = index;
+                break;
+            }
+        }
+        Ok(())
+    }
 }
 
 use crate::tabs::import::logic::Importer;
+use crate::utils::sql::open_db;
+use crate::utils::sql::trash::purge_trash;
+use crate::utils::check::check;
 use std::sync::{Arc, Mutex};
 
 
@@ -132,32 +411,68 @@ pub struct App {
     pub should_quit: bool,
     pub display_help: bool,
     pub appdata: AppData,
+    /// Shown on top of everything until the next keypress.
+    pub error: Option<String>,
+    pub search: Option<SearchWidget>,
 }
 
 impl App {
-    pub fn new(display_help: bool, paths: SpekiPaths) -> App {
-        let conn = Arc::new(Mutex::new(
-            Connection::open(&paths.database).expect("Failed to connect to database."),
-        ));
-        let config = Config::new(&paths);
+    pub fn new(display_help: bool, paths: SpekiPaths) -> crate::error::Result<App> {
+        let conn = Arc::new(Mutex::new(open_db(&paths.database)?));
+        let mut error = None;
+        let config = Config::new(&paths).unwrap_or_else(|err| {
+            error = Some(format!("{}\n\nUsing the default settings for now.", err));
+            Config::fallback()
+        });
+        purge_trash(&conn, config.trash_retention_days)?;
+        if config.startup_check != StartupCheck::None {
+            let report = check(&conn)?;
+            if !report.is_empty() {
+                let outcome = if config.startup_check == StartupCheck::Repair {
+                    report.repair(&conn)?;
+                    "They've all been repaired."
+                } else {
+                    "Run `speki check --repair` to fix them."
+                };
+                let notice = format!("{}\n{}", report.summary(10), outcome);
+                error = Some(match error {
+                    Some(error) => format!("{}\n\n{}", error, notice),
+                    None => notice,
+                });
+            }
+        }
         let audio = Audio::new();
-        let tabs = TabsState::new(&conn, &audio);
+        let tabs = TabsState::new(&conn, &audio, &config)?;
         let appdata = AppData {
             conn,
             audio,
             config,
             paths,
+            reload: None,
+            switch_to: None,
         };
 
-        App {
+        Ok(App {
             tabs,
             display_help,
             should_quit: false,
             appdata,
-        }
+            error,
+            search: None,
+        })
     }
 
     pub fn keyhandler(&mut self, key: MyKey) {
+        if self.error.take().is_some() {
+            return;
+        }
+        if let Err(err) = self.search_keyhandler(&key) {
+            self.error = Some(err.to_string());
+            return;
+        }
+        if self.search.is_some() {
+            return;
+        }
         match key {
             MyKey::Tab => self.tabs.next(),
             MyKey::BackTab => self.tabs.previous(),
@@ -165,6 +480,7 @@ impl App {
             MyKey::BackSwapTab => self.tabs.swap_left(),
             MyKey::F(1) => self.display_help = !self.display_help,
             MyKey::Alt('q') => self.should_quit = true,
+            MyKey::Ctrl('f') => self.search = Some(SearchWidget::new()),
             MyKey::Alt('m') => {
                 if self.appdata.audio.is_some(){
                     self.appdata.audio = None;
@@ -172,15 +488,64 @@ impl App {
                     self.appdata.audio = Audio::new();
                 }
             },
-            key => self.tabs.keyhandler(&self.appdata, key),
+            key => {
+                if let Err(err) = self.tabs.keyhandler(&mut self.appdata, key) {
+                    self.error = Some(err.to_string());
+                }
+            }
+        };
+        if let Some(message) = self.appdata.reload.take() {
+            self.error = Some(match self.reload_tabs() {
+                Ok(()) => message,
+                Err(err) => err.to_string(),
+            });
+        }
+    }
+
+    fn reload_tabs(&mut self) -> crate::error::Result<()> {
+        let index = self.tabs.index;
+        self.tabs = TabsState::new(&self.appdata.conn, &self.appdata.audio, &self.appdata.config)?;
+        self.tabs.index = index;
+        Ok(())
+    }
+
+    fn search_keyhandler(&mut self, key: &MyKey) -> crate::error::Result<()> {
+        let search = match &mut self.search {
+            Some(search) => search,
+            None => return Ok(()),
         };
+        if let PopUpStatus::Finished = search.keyhandler(&self.appdata.conn, key.clone())? {
+            let chosen = search.chosen;
+            self.search = None;
+            if let Some(target) = chosen {
+                self.tabs.jump_to(&mut self.appdata, target)?;
+            }
+        }
+        Ok(())
     }
 
     pub fn render(&mut self, f: &mut Frame<MyType>) {
+        if let Err(err) = self.tabs.tick(&mut self.appdata) {
+            self.error = Some(err.to_string());
+        }
         let mut area = f.size();
         area = self.render_help(f, area);
         area = self.render_tab_menu(f, area);
         self.tabs.render(f, &self.appdata, area);
+        if let Some(search) = &mut self.search {
+            let mut area = centered_rect(80, 70, f.size());
+            f.render_widget(Clear, area);
+            area.x += 2;
+            area.y += 1;
+            area.width = area.width.saturating_sub(4);
+            area.height = area.height.saturating_sub(2);
+            search.render(f, area);
+        }
+        if let Some(error) = &self.error {
+            let area = centered_rect(60, 30, f.size());
+            f.render_widget(Clear, area);
+            draw_message(f, area, &format!("{} (press any key to continue)", error));
+        }
     }
 
     fn render_tab_menu(&self, f: &mut Frame<MyType>, area: Rect) -> Rect {
@@ -225,6 +590,7 @@ next tab: Tab,
 previous tab: Shift+Tab,
 move between widgets: Alt + arrow-keys (or vim-keys)
 quit: Alt+q
+search cards and texts: Ctrl+f
 
 "#
         .to_string();
@@ -243,9 +609,9 @@ use crate::MyKey;
 pub trait Tab {
     fn keyhandler(
         &mut self,
-        appdata: &AppData,
+        appdata: &mut AppData,
         key: MyKey,
-    );
+    ) -> crate::error::Result<()>;
     fn render(
         &mut self,
         f: &mut Frame<MyType>,
@@ -256,4 +622,16 @@ pub trait Tab {
     fn get_manual(&self) -> String {
         String::new()
     }
+    /// Called before every frame, to pick up work that finished in the background.
+    fn tick(&mut self, _appdata: &mut AppData) -> crate::error::Result<()> {
+        Ok(())
+    }
+    /// Shows a search result, returns false if the tab has nowhere to show it.
+    fn jump_to(
+        &mut self,
+        _appdata: &mut AppData,
+        _target: Target,
+    ) -> crate::error::Result<bool> {
+        Ok(false)
+    }
 }
@@ -0,0 +1,102 @@
+use std::sync::{Arc, Mutex};
+
+use crate::app::Config;
+use crate::error::Result;
+use crate::utils::check::check;
+use crate::utils::forecast::forecast;
+use crate::utils::interval::recompute_all;
+use crate::utils::leech::leech_report;
+use crate::utils::optimizer::optimize_and_save;
+use crate::utils::sql::open_db;
+use crate::SpekiPaths;
+
+const USAGE: &str = r#"usage: speki [--profile name] [--data-dir path] [command]
+
+Without a command speki starts the interactive interface.
+
+options:
+    --profile name
+                use the collection of the given profile, made if it doesn't exist yet.
+                SPEKI_PROFILE does the same, the flag wins if both are set
+    --data-dir path
+                keep the database, media, backups and config file in the given folder
+
+commands:
+    optimize    fit the scheduler parameters to your review history
+    forecast [days] [new per day]
+                project the amount of reviews for the coming days
+    leeches     list the cards that you keep forgetting
+    recompute   replay every review through the current scheduler, after changing it
+                or its parameters
+    check [--repair]
+                look for inconsistencies in the database, and fix them with --repair
+    help        show this message
+"#;
+
+/// Runs a subcommand without starting the TUI.
+pub fn run(args: &[String], paths: &SpekiPaths) {
+    if let Err(err) = execute(args, paths) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+fn execute(args: &[String], paths: &SpekiPaths) -> Result<()> {
+    let conn = Arc::new(Mutex::new(open_db(&paths.database)?));
+    match args[0].as_str() {
+        "optimize" => {
+            let mut config = Config::new(paths)?;
+            println!("{}", optimize_and_save(&conn, &mut config, &paths.config)?);
+        }
+        "forecast" => {
+            let config = Config::new(paths)?;
+            let days = parse_arg(args, 1, config.forecast.days);
+            let new_per_day = parse_arg(args, 2, config.forecast.new_per_day);
+            print!("{}", forecast(&conn, &config, days, new_per_day)?);
+        }
+        "leeches" => print!("{}", leech_report(&conn)?),
+        "recompute" => {
+            let config = Config::new(paths)?;
+            print!("{}", recompute_all(&conn, &config)?);
+        }
+        "check" => {
+            let repair = args.get(1).map(|arg| arg == "--repair").unwrap_or(false);
+            let report = check(&conn)?;
+            print!("{}", report);
+            if repair && !report.is_empty() {
+                report.repair(&conn)?;
+                println!("repaired");
+            } else if !report.is_empty() {
+                println!("run `speki check --repair` to fix them");
+            }
+        }
+        "help" | "--help" | "-h" => print!("{}", USAGE),
+        other => {
+            eprint!("unknown command: {}\n\n{}", other, USAGE);
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+/// Removes `--name value` from the arguments and returns the value.
+pub fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let idx = args.iter().position(|arg| arg == name)?;
+    if idx + 1 == args.len() {
+        eprint!("{} needs a value\n\n{}", name, USAGE);
+        std::process::exit(1);
+    }
+    let value = args.remove(idx + 1);
+    args.remove(idx);
+    Some(value)
+}
+
+fn parse_arg<T: std::str::FromStr>(args: &[String], idx: usize, default: T) -> T {
+    match args.get(idx) {
+        Some(arg) => arg.parse().unwrap_or_else(|_| {
+            eprint!("invalid argument: {}\n\n{}", arg, USAGE);
+            std::process::exit(1);
+        }),
+        None => default,
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt;
+
+/// Everything that can go wrong while using speki, short of a bug.
+#[derive(Debug)]
+pub enum SpekiError {
+    Database(rusqlite::Error),
+    Io(std::io::Error),
+    /// The config file couldn't be read or parsed.
+    Config(String),
+    Other(String),
+}
+
+pub type Result<T> = std::result::Result<T, SpekiError>;
+
+impl fmt::Display for SpekiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpekiError::Database(err) => write!(f, "database error: {}", err),
+            SpekiError::Io(err) => write!(f, "io error: {}", err),
+            SpekiError::Config(msg) => write!(f, "invalid config file: {}", msg),
+            SpekiError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SpekiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SpekiError::Database(err) => Some(err),
+            SpekiError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for SpekiError {
+    fn from(err: rusqlite::Error) -> Self {
+        SpekiError::Database(err)
+    }
+}
+
+impl From<std::io::Error> for SpekiError {
+    fn from(err: std::io::Error) -> Self {
+        SpekiError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for SpekiError {
+    fn from(err: toml::de::Error) -> Self {
+        SpekiError::Config(err.to_string())
+    }
+}
+
+impl From<anyhow::Error> for SpekiError {
+    fn from(err: anyhow::Error) -> Self {
+        SpekiError::Other(format!("{:#}", err))
+    }
+}
@@ -4,14 +4,20 @@
 use std::fs::File;
 use std::io::prelude::*;
 pub mod app;
+pub mod cli;
+pub mod error;
 pub mod tabs;
 pub mod utils;
 pub mod widgets;
-use chrono::prelude::*;
 ///pub mod tabs;
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
 //use tabs::MyType;
-use crate::app::App;
+use crate::app::{App, AppData};
+use crate::error::SpekiError;
+use crate::utils::backup::{prune, snapshot};
 use crate::utils::sql::init_db;
 use crossterm::{
     event::{
@@ -27,6 +33,7 @@ pub type MyType = CrosstermBackend<std::io::Stdout>;
 
 #[derive(Clone)]
 pub struct SpekiPaths {
+    pub home: PathBuf,
     pub base: PathBuf,
     pub database: PathBuf,
     pub media: PathBuf,
@@ -39,33 +46,114 @@ pub struct SpekiPaths {
 impl SpekiPaths {
   const DEFAULTCONFIG: &'static str = r#"
 #gptkey = ""
+
+# memory model used for scheduling, either "classic" or "fsrs"
+#scheduler = "classic"
+
+# cards are due for review once their estimated recall drops below this
+#retention = 0.9
+
+# what happens to the cards that depend on a card you fail: "none", "weaken" or "requeue"
+# weaken multiplies their stability by dependent_weakening until their next review, requeue adds the ones not yet reviewed today to the session
+#on_failed_dependency = "none"
+#dependent_weakening = 0.5
+
+# daily caps on new cards and on reviews, unlimited when left out
+#new_per_day = 20
+#reviews_per_day = 200
+
+# a card becomes a leech once you've forgotten it this many times, after which it's
+# tagged "leech", and either left at that, suspended, or made unfinished to be rewritten
+#leech_threshold = 8
+#leech_action = "tag"
+
+# only show one card a day from the same incremental reading source or anki note,
+# so they don't give away each other's answers
+#bury_siblings = true
+
+# minutes until failed and new cards come back in the same session, one step at a time.
+# failing starts over from the first step, an empty list turns this off
+#learning_steps = [1, 10]
+
+# order of the cards in a review session: random, weakest, topic, dependencies or oldest.
+# can be switched during a session with Alt+o in the review tab
+#review_order = "random"
+
+# days that deleted cards, topics and texts stay in the trash before they're gone for good
+#trash_retention_days = 30
+
+# look for inconsistencies in the database on every start: "none", "report" or "repair"
+#startup_check = "none"
+
+# settings can be overridden per topic, subtopics inherit them.
+# a daily cap on a topic is shared by the topic and all its subtopics
+#[topics."my topic"]
+#retention = 0.95
+#new_per_day = 5
+
+# assumptions for the workload forecast
+#[forecast]
+#days = 30
+#new_per_day = 10
+
+# how many snapshots of the database to keep, one for each of the last so many days, weeks and months
+#[backup]
+#daily = 7
+#weekly = 4
+#monthly = 12
+
+#[classic]
+#decay = 0.9
+#grade_factors = [0.25, 0.5, 2.0, 4.0]
         "#;
-    fn new(mut home: PathBuf) -> Self {
-        let mut configpath = home.clone();
-        if cfg!(windows) {
-            home.push(".speki/");
-            if !std::path::Path::new(&home).exists() {
-                std::fs::create_dir(&home).unwrap();
-            }
-            configpath.push("config.toml");
-            if !std::path::Path::new(&configpath).exists() {
-                let mut file = File::create(&configpath).unwrap();
-                file.write_all(Self::DEFAULTCONFIG.as_bytes()).unwrap();
-            }
+    /// Where a profile keeps its data and its config file. The default profile (`None`) uses
+    /// the usual places, named ones get a folder of their own with the config file inside.
+    fn locations(home: &Path, profile: Option<&str>) -> (PathBuf, PathBuf) {
+        let (base, config) = if cfg!(windows) {
+            (home.join(".speki/"), home.join("config.toml"))
         } else {
-            home.push(".local/share/speki/");
-            std::fs::create_dir_all(&home).unwrap();
-            configpath.push(".config/speki/config.toml");
-            std::fs::create_dir_all(&configpath.parent().unwrap()).unwrap();
-            if !std::path::Path::new(&configpath).exists() {
-                let mut file = File::create(&configpath).unwrap();
-                file.write_all(Self::DEFAULTCONFIG.as_bytes()).unwrap();
+            (
+                home.join(".local/share/speki/"),
+                home.join(".config/speki/config.toml"),
+            )
+        };
+        match profile {
+            Some(name) => {
+                let base = base.join("profiles").join(name);
+                let config = base.join("config.toml");
+                (base, config)
             }
+            None => (base, config),
         }
-        let mut database = home.clone();
-        let mut media = home.clone();
-        let mut tempfolder = home.clone();
-        let mut backups = home.clone();
+    }
+
+    pub fn new(home: PathBuf, profile: Option<&str>) -> io::Result<Self> {
+        let (base, config) = Self::locations(&home, profile);
+        Self::with_dirs(home, base, config)
+    }
+
+    /// Everything in the given folder, config file included, regardless of profiles.
+    pub fn data_dir(home: PathBuf, dir: PathBuf) -> io::Result<Self> {
+        // media paths get stored in the database, they have to work from any working directory
+        let dir = std::path::absolute(&dir).unwrap_or(dir);
+        let config = dir.join("config.toml");
+        Self::with_dirs(home, dir, config)
+    }
+
+    /// Creates the folders and a default config file where they're missing.
+    fn with_dirs(home: PathBuf, base: PathBuf, configpath: PathBuf) -> io::Result<Self> {
+        std::fs::create_dir_all(&base)?;
+        if let Some(dir) = configpath.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        if !configpath.exists() {
+            let mut file = File::create(&configpath)?;
+            file.write_all(Self::DEFAULTCONFIG.as_bytes())?;
+        }
+        let mut database = base.clone();
+        let mut media = base.clone();
+        let mut tempfolder = base.clone();
+        let mut backups = base.clone();
 
         database.push("dbflash.db");
         media.push("media/");
@@ -75,23 +163,116 @@ impl SpekiPaths {
         let mut downloc = tempfolder.clone();
         downloc.push("ankitemp.apkg");
 
-        Self {
-            base: home,
+        Ok(Self {
+            home,
+            base,
             database,
             media,
             tempfolder,
             downloc,
             backups,
             config: configpath,
-        }
+        })
+    }
+
+    /// Profile names can't be empty or reach outside the profiles folder.
+    pub fn valid_profile(name: &str) -> bool {
+        !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\'])
+    }
+
+    /// "default" followed by every named profile, alphabetically.
+    pub fn profiles(&self) -> Vec<String> {
+        let (base, _) = Self::locations(&self.home, None);
+        let mut profiles: Vec<String> = std::fs::read_dir(base.join("profiles"))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        profiles.sort();
+        profiles.insert(0, String::from("default"));
+        profiles
+    }
+
+    /// The paths of a profile from `profiles`, in the same home folder.
+    pub fn switch_profile(&self, name: &str) -> io::Result<Self> {
+        Self::new(self.home.clone(), Self::profile_arg(name))
+    }
+
+    pub fn is_profile(&self, name: &str) -> bool {
+        Self::locations(&self.home, Self::profile_arg(name)).0 == self.base
+    }
+
+    /// "default" is what the default profile goes by.
+    fn profile_arg(name: &str) -> Option<&str> {
+        Some(name).filter(|name| *name != "default")
     }
 }
 
 fn main() -> Result<()> {
     env::set_var("RUST_BACKTRACE", "1");
 
-    let paths = SpekiPaths::new(home::home_dir().unwrap());
-    let is_new_db = init_db(&paths.database).unwrap();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let profile = cli::take_option(&mut args, "--profile")
+        .or_else(|| env::var("SPEKI_PROFILE").ok())
+        .filter(|name| !name.is_empty());
+    let data_dir = cli::take_option(&mut args, "--data-dir");
+    let home = home::home_dir().unwrap();
+    let paths = match (data_dir, profile) {
+        (Some(dir), _) => SpekiPaths::data_dir(home, PathBuf::from(dir)),
+        (None, Some(name)) if !SpekiPaths::valid_profile(&name) => {
+            eprintln!("invalid profile name: {}", name);
+            std::process::exit(1);
+        }
+        (None, Some(name)) => SpekiPaths::new(home, SpekiPaths::profile_arg(&name)),
+        (None, None) => SpekiPaths::new(home, None),
+    };
+    let paths = match paths {
+        Ok(paths) => paths,
+        Err(err) => {
+            eprintln!("failed to set up the data folder: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let is_new_db = match init_db(&paths) {
+        Ok(is_new_db) => is_new_db,
+        Err(err) => {
+            eprintln!("failed to open the database: {:#}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if !args.is_empty() {
+        cli::run(&args, &paths);
+        return Ok(());
+    }
+
+    // create app before taking over the terminal, so a failure can be printed normally
+    let app = match App::new(is_new_db, paths) {
+        Ok(app) => app,
+        Err(err) => {
+            eprintln!("failed to start speki: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // a bug shouldn't leave the terminal in raw mode. Only the main thread owns the terminal,
+    // the worker threads report their failures back to it instead.
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().name() != Some("main") {
+            return default_hook(info);
+        }
+        let _ = disable_raw_mode();
+        let _ = execute!(
+            io::stdout(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            DisableBracketedPaste,
+        );
+        default_hook(info);
+    }));
 
     // setup terminal
     enable_raw_mode()?;
@@ -105,8 +286,7 @@ fn main() -> Result<()> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    // create app and run it
-    let app = App::new(is_new_db, paths);
+    // run it
     let res = run_app(&mut terminal, app);
 
     // restore terminal
@@ -139,9 +319,17 @@ fn run_app(terminal: &mut Terminal<MyType>, mut app: App) -> io::Result<()> {
             }
 
             if app.should_quit {
-                backup(&app.appdata.paths);
+                backup(&app.appdata)?;
                 return Ok(());
             }
+
+            if let Some(paths) = app.appdata.switch_to.take() {
+                backup(&app.appdata)?;
+                match switch_profile(app.display_help, paths) {
+                    Ok(new) => app = new,
+                    Err(err) => app.error = Some(err.to_string()),
+                }
+            }
         }
     }
 }
@@ -250,14 +438,15 @@ impl MyKey {
     }
 }
 
-fn backup(paths: &SpekiPaths) {
-    let mut backup_path = paths.backups.clone();
+fn switch_profile(display_help: bool, paths: SpekiPaths) -> crate::error::Result<App> {
+    let is_new_db = init_db(&paths)?;
+    App::new(display_help || is_new_db, paths)
+}
 
-    if !std::path::Path::new(&backup_path).exists() {
-        std::fs::create_dir(&backup_path).unwrap();
-    }
-    let now: DateTime<Utc> = Utc::now();
-    let filename = format!("backup_{}_dbflash.db", now.format("%d_%m_%Y"));
-    backup_path.push(filename);
-    std::fs::copy(&paths.database, backup_path).unwrap();
+fn backup(appdata: &AppData) -> io::Result<()> {
+    let to_io = |err: SpekiError| io::Error::other(err.to_string());
+    let backups = &appdata.paths.backups;
+    snapshot(&appdata.conn.lock().unwrap(), backups, "quit").map_err(to_io)?;
+    prune(backups, &appdata.config.backup).map_err(to_io)?;
+    Ok(())
 }
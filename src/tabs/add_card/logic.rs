@@ -1,10 +1,11 @@
 use crate::app::AppData;
 use crate::app::Tab;
+use crate::error::Result;
 use crate::utils::card::CardType;
 use crate::utils::misc::get_gpt3_response;
 use crate::utils::misc::{split_leftright, split_updown};
 use crate::utils::{aliases::*, sql::fetch::load_inc_title};
-use crate::utils::{card::Card, sql::fetch::fetch_card};
+use crate::utils::{card::Card, sql::fetch::fetch_card, sql::tags::parse_tags};
 use crate::widgets::find_card::FindCardWidget;
 use crate::widgets::message_box::draw_message;
 use crate::widgets::textinput::Field;
@@ -26,6 +27,7 @@ pub enum DepState {
 pub enum TextSelect {
     Question, // Bool indicates if youre in text-editing mode
     Answer,
+    Tags,
     Topic,
     ChooseCard(FindCardWidget),
 }
@@ -37,6 +39,7 @@ pub struct NewCard {
     pub prompt: String,
     pub question: Field,
     pub answer: Field,
+    pub tags: Field,
     pub state: DepState,
     pub topics: TopicList,
     pub selection: TextSelect,
@@ -45,18 +48,19 @@ pub struct NewCard {
 use std::sync::{Arc, Mutex};
 
 impl NewCard {
-    pub fn new(conn: &Arc<Mutex<Connection>>, state: DepState) -> NewCard {
+    pub fn new(conn: &Arc<Mutex<Connection>>, state: DepState) -> Result<NewCard> {
         let mut topics = TopicList::new(conn);
         topics.next();
 
-        NewCard {
-            prompt: NewCard::make_prompt(&state, conn),
+        Ok(NewCard {
+            prompt: NewCard::make_prompt(&state, conn)?,
             question: Field::new(),
             answer: Field::new(),
+            tags: Field::new(),
             state,
             topics,
             selection: TextSelect::Question,
-        }
+        })
     }
 
     pub fn navigate(&mut self, dir: crate::Direction) {
@@ -67,43 +71,44 @@ impl NewCard {
             (Question, Down) => self.selection = Answer,
             (Answer, Up) => self.selection = Question,
             (Answer, Right) => self.selection = Topic,
+            (Answer, Down) => self.selection = Tags,
+            (Tags, Up) => self.selection = Answer,
+            (Tags, Right) => self.selection = Topic,
             (Topic, Left) => self.selection = Question,
             (_, _) => {}
         }
     }
 
-    fn make_prompt(state: &DepState, conn: &Arc<Mutex<Connection>>) -> String {
+    fn make_prompt(state: &DepState, conn: &Arc<Mutex<Connection>>) -> Result<String> {
         let mut prompt = String::new();
         match state {
             DepState::None => {
                 prompt.push_str("Add new card");
-                prompt
             }
             DepState::NewDependency(idx) => {
                 prompt.push_str("Add new dependency for ");
-                let card = fetch_card(conn, *idx);
+                let card = fetch_card(conn, *idx)?;
                 prompt.push_str(&card.question);
-                prompt
             }
             DepState::NewDependent(idx) => {
                 prompt.push_str("Add new dependent of: ");
-                let card = fetch_card(conn, *idx);
+                let card = fetch_card(conn, *idx)?;
                 prompt.push_str(&card.question);
-                prompt
             }
             DepState::NewChild(id) => {
                 prompt.push_str("Add new child of source: ");
-                let title = load_inc_title(conn, *id, 15).unwrap();
+                let title = load_inc_title(conn, *id, 15)?;
                 prompt.push_str(&title);
-                prompt
             }
         }
+        Ok(prompt)
     }
 
-    pub fn submit_card(&mut self, conn: &Arc<Mutex<Connection>>, iscompleted: bool) {
+    pub fn submit_card(&mut self, conn: &Arc<Mutex<Connection>>, iscompleted: bool) -> Result<()> {
         let question = self.question.return_text();
         let answer = self.answer.return_text();
         let topic = self.topics.get_selected_id().unwrap();
+        let tags = parse_tags(&self.tags.return_text());
         let source = if let DepState::NewChild(incid) = self.state {
             incid
         } else {
@@ -120,6 +125,7 @@ impl NewCard {
             .question(question)
             .answer(answer)
             .topic(topic)
+            .tags(tags)
             .source(source)
             .cardtype(status);
 
@@ -136,9 +142,10 @@ impl NewCard {
             DepState::NewChild(_id) => {}
         }
 
-        card.save_card(conn);
+        card.save_card(conn)?;
         //self.reset(DepState::None, conn);
-        *self = Self::new(conn, DepState::None);
+        *self = Self::new(conn, DepState::None)?;
+        Ok(())
     }
 
     pub fn uprow(&mut self) {}
@@ -158,30 +165,34 @@ Topic of card is as selected in the topic widget.
 
 Upper textbox is question, lower is answer.
 
+Below them go the tags of the card, separated by spaces.
+
 add card as finished: Alt+f
 Add card as unfinished: Alt+u    
 
         "#
         .to_string()
     }
-    fn keyhandler(&mut self, appdata: &AppData, key: MyKey) {
+    fn keyhandler(&mut self, appdata: &mut AppData, key: MyKey) -> Result<()> {
         use MyKey::*;
         use TextSelect::*;
         match (&self.selection, key) {
             (_, Nav(dir)) => self.navigate(dir),
-            (_, Alt('f')) => self.submit_card(&appdata.conn, true),
-            (_, Alt('u')) => self.submit_card(&appdata.conn, false),
+            (_, Alt('f')) => self.submit_card(&appdata.conn, true)?,
+            (_, Alt('u')) => self.submit_card(&appdata.conn, false)?,
             (_, Alt('g')) => {
                 if let Some(key) = &appdata.config.gptkey {
-                    let answer = get_gpt3_response(key, &self.question.return_text());
+                    let answer = get_gpt3_response(key, &self.question.return_text())?;
                     self.answer.replace_text(answer);
                 }
             }
             (Question, key) => self.question.keyhandler(key),
             (Answer, key) => self.answer.keyhandler(key),
-            (Topic, key) => self.topics.keyhandler(key, &appdata.conn),
+            (Tags, key) => self.tags.keyhandler(key),
+            (Topic, key) => self.topics.keyhandler(key, &appdata.conn)?,
             (_, _) => {}
         }
+        Ok(())
     }
     fn render(&mut self, f: &mut Frame<MyType>, _appdata: &AppData, area: Rect) {
         let chunks = split_leftright([75, 15], area);
@@ -196,7 +207,7 @@ Add card as unfinished: Alt+u
             Style::default(),
         );
 
-        let chunks = split_updown([10, 37, 37], left);
+        let chunks = split_updown([10, 37, 37, 10], left);
 
         draw_message(f, chunks[0], self.prompt.as_str());
         self.question.render(
@@ -206,5 +217,10 @@ Add card as unfinished: Alt+u
         );
         self.answer
             .render(f, chunks[2], matches!(&self.selection, TextSelect::Answer));
+        // the mode would show otherwise
+        self.tags.title = String::from("Tags");
+        self.tags.stickytitle = true;
+        self.tags
+            .render(f, chunks[3], matches!(&self.selection, TextSelect::Tags));
     }
 }
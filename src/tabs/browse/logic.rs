@@ -84,7 +84,7 @@ impl Tab for Browse {
         "Browse".to_string()
     }
 
-    fn keyhandler(&mut self, appdata: &crate::app::AppData, key: MyKey) {
+    fn keyhandler(&mut self, appdata: &mut crate::app::AppData, key: MyKey) {
         use MyKey::*;
         use Selection::*;
         if let Nav(dir) = key{
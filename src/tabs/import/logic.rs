@@ -7,7 +7,7 @@ use crate::{utils::misc::split_updown, Direction, SpekiPaths};
 use std::fs::File;
 
 use crate::widgets::ankimporter::Ankimporter;
-use crate::widgets::load_cards::{ImportProgress, LoadState, Template};
+use crate::widgets::load_cards::{ImportProgress, ImportStatus, LoadState, Template};
 use crate::MyType;
 use reqwest;
 use rusqlite::Connection;
@@ -17,11 +17,13 @@ use tui::{
     style::Color,
 };
 
+use crate::utils::backup::snapshot;
 use crate::widgets::ankimporter::ShouldQuit;
 use crate::widgets::filepicker::{FilePicker, PickState};
 use regex::Regex;
 use reqwest::header;
 use std::io::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::mpsc::Receiver;
 use std::thread;
@@ -155,7 +157,14 @@ impl Selected {
 
 struct Unzipper {
     rx: mpsc::Receiver<UnzipStatus>,
-    name: String,
+    /// Why the deck couldn't be unzipped or loaded, shown until a key is pressed.
+    failed: Option<String>,
+}
+
+struct Importing {
+    rx: mpsc::Receiver<ImportProgress>,
+    cancel: Arc<AtomicBool>,
+    progress: ImportProgress,
 }
 
 use crate::widgets::load_cards::UnzipStatus;
@@ -164,7 +173,7 @@ enum Menu {
     Anki(Ankimporter),
     Local(FilePicker),
     LoadCards(Template),
-    ImportAnki(mpsc::Receiver<ImportProgress>),
+    ImportAnki(Importing),
     Unzipping(Unzipper),
 }
 
@@ -241,7 +250,7 @@ If you don't want to import the selected deck, press escape!
         "#.to_string()
     }
 
-    fn keyhandler(&mut self, appdata: &AppData, key: MyKey) {
+    fn keyhandler(&mut self, appdata: &mut AppData, key: MyKey) -> crate::error::Result<()> {
         match &mut self.menu {
             Menu::Main => self.main_keyhandler(&appdata.conn, key),
             Menu::Anki(ankimporter) => match &ankimporter.should_quit {
@@ -265,31 +274,63 @@ If you don't want to import the selected deck, press escape!
                         foldername.pop();
                         foldername.pop();
                         let foldername = foldername.rsplit_once('/').unwrap().1.to_string();
-                        let template = Template::new(&appdata.conn, foldername, &appdata.paths);
-                        self.menu = Menu::LoadCards(template);
+                        match Template::new(&appdata.conn, foldername, &appdata.paths) {
+                            Ok(template) => self.menu = Menu::LoadCards(template),
+                            Err(err) => {
+                                self.menu = Menu::Main;
+                                return Err(err.into());
+                            }
+                        }
                     }
                 };
             }
             Menu::LoadCards(tmpl) => match tmpl.state {
                 LoadState::OnGoing => {
-                    tmpl.keyhandler(&appdata.conn, key, &appdata.audio);
+                    tmpl.keyhandler(&appdata.conn, key, &appdata.audio)?;
                     if let LoadState::Importing = tmpl.state {
+                        let conn = appdata.conn.lock().unwrap();
+                        if let Err(err) = snapshot(&conn, &appdata.paths.backups, "import") {
+                            tmpl.state = LoadState::OnGoing;
+                            return Err(err);
+                        }
+                        drop(conn);
                         let mut tmpclone = tmpl.clone();
                         let (tx, rx): (mpsc::SyncSender<ImportProgress>, Receiver<ImportProgress>) =
                             mpsc::sync_channel(5);
                         let connclone = Arc::clone(&appdata.conn);
+                        let cancel = Arc::new(AtomicBool::new(false));
+                        let cancelclone = Arc::clone(&cancel);
+                        let total = tmpl.card_count();
                         thread::spawn(move || {
-                            tmpclone.import_cards(connclone, tx);
+                            tmpclone.import_cards(connclone, tx, cancelclone);
+                        });
+                        self.menu = Menu::ImportAnki(Importing {
+                            rx,
+                            cancel,
+                            progress: ImportProgress {
+                                curr_index: 0,
+                                total,
+                                status: ImportStatus::Ongoing,
+                            },
                         });
-                        self.menu = Menu::ImportAnki(rx);
                     }
                 }
                 LoadState::Importing => {}
                 LoadState::Finished => self.menu = Menu::Anki(Ankimporter::new()),
             },
-            Menu::ImportAnki(_) => {}
-            Menu::Unzipping(_) => {}
+            Menu::ImportAnki(importing) => match (&importing.progress.status, key) {
+                (ImportStatus::Ongoing, MyKey::Esc) => importing.cancel.store(true, Ordering::Relaxed),
+                (ImportStatus::Ongoing, _) => {}
+                (_, MyKey::Enter) | (_, MyKey::Esc) => self.menu = Menu::Anki(Ankimporter::new()),
+                (_, _) => {}
+            },
+            Menu::Unzipping(unzipper) => {
+                if unzipper.failed.is_some() {
+                    self.menu = Menu::Anki(Ankimporter::new());
+                }
+            }
         }
+        Ok(())
     }
 
     fn render(&mut self, f: &mut tui::Frame<MyType>, appdata: &AppData, area: tui::layout::Rect) {
@@ -305,32 +346,37 @@ If you don't want to import the selected deck, press escape!
                     ShouldQuit::Takethis(deckname) => {
                         let (tx, rx): (mpsc::Sender<UnzipStatus>, Receiver<UnzipStatus>) =
                             mpsc::channel();
+                        let connclone = Arc::clone(&appdata.conn);
                         let threadpaths = appdata.paths.clone();
                         let deckname = deckname.to_string();
-                        let anotherone = deckname.clone();
                         thread::spawn(move || {
-                            Template::unzip_deck(threadpaths, deckname.clone(), tx);
-                        });
-                        self.menu = Menu::Unzipping(Unzipper {
-                            rx,
-                            name: anotherone,
+                            Template::unzip_deck(connclone, threadpaths, deckname, tx);
                         });
+                        self.menu = Menu::Unzipping(Unzipper { rx, failed: None });
                     }
                 };
             }
 
             Menu::Unzipping(unzipper) => {
-                if let Ok(unstat) = unzipper.rx.recv() {
-                    if let UnzipStatus::Ongoing(msg) = unstat {
-                        draw_message(f, area, &msg);
-                    } else {
-                        let tmpl =
-                            Template::new(&appdata.conn, unzipper.name.clone(), &appdata.paths);
-                        self.menu = Menu::LoadCards(tmpl);
+                if unzipper.failed.is_none() {
+                    match unzipper.rx.recv() {
+                        Ok(UnzipStatus::Ongoing(msg)) => draw_message(f, area, &msg),
+                        Ok(UnzipStatus::Done(tmpl)) => self.menu = Menu::LoadCards(tmpl),
+                        Ok(UnzipStatus::Failed(err)) => unzipper.failed = Some(err),
+                        Err(_) => {
+                            unzipper.failed = Some("the unzipping stopped unexpectedly".to_string())
+                        }
                     }
-                } else {
-                    let tmpl = Template::new(&appdata.conn, unzipper.name.clone(), &appdata.paths);
-                    self.menu = Menu::LoadCards(tmpl);
+                }
+                if let Menu::Unzipping(Unzipper {
+                    failed: Some(err), ..
+                }) = &self.menu
+                {
+                    draw_message(
+                        f,
+                        area,
+                        &format!("Couldn't load the deck: {}\n\nPress any key to go back", err),
+                    );
                 }
             }
 
@@ -341,37 +387,56 @@ If you don't want to import the selected deck, press escape!
                     self.menu = Menu::Anki(aim);
                 }
             }
-            Menu::ImportAnki(rx) => {
-                if let Ok(prog) = rx.recv() {
-                    let rightcol = Layout::default()
-                        .direction(Vertical)
-                        .constraints(
-                            [
-                                Constraint::Ratio(1, 3),
-                                Constraint::Ratio(1, 3),
-                                Constraint::Ratio(1, 3),
-                            ]
-                            .as_ref(),
-                        )
-                        .split(area);
-                    let mut progbar = rightcol[0];
-
-                    progbar.height = std::cmp::min(progbar.height, 5);
-                    crate::widgets::progress_bar::progress_bar(
-                        f,
-                        prog.curr_index as u32,
-                        prog.total as u32,
-                        Color::LightMagenta,
-                        progbar,
-                        "Importing cards..",
-                    );
-
-                    if prog.curr_index == prog.total - 1 {
-                        self.menu = Menu::Anki(Ankimporter::new());
+            Menu::ImportAnki(importing) => {
+                loop {
+                    match importing.rx.try_recv() {
+                        Ok(progress) => importing.progress = progress,
+                        Err(mpsc::TryRecvError::Empty) => break,
+                        Err(mpsc::TryRecvError::Disconnected) => {
+                            if importing.progress.status == ImportStatus::Ongoing {
+                                importing.progress.status =
+                                    ImportStatus::Failed("the import stopped unexpectedly".to_string());
+                            }
+                            break;
+                        }
                     }
-                } else {
-                    self.menu = Menu::Anki(Ankimporter::new());
                 }
+                let prog = &importing.progress;
+                let msg = match &prog.status {
+                    ImportStatus::Ongoing => {
+                        let rightcol = Layout::default()
+                            .direction(Vertical)
+                            .constraints(
+                                [
+                                    Constraint::Ratio(1, 3),
+                                    Constraint::Ratio(1, 3),
+                                    Constraint::Ratio(1, 3),
+                                ]
+                                .as_ref(),
+                            )
+                            .split(area);
+                        let mut progbar = rightcol[0];
+
+                        progbar.height = std::cmp::min(progbar.height, 5);
+                        crate::widgets::progress_bar::progress_bar(
+                            f,
+                            prog.curr_index as u32,
+                            prog.total as u32,
+                            Color::LightMagenta,
+                            progbar,
+                            "Importing cards.. (Esc to cancel)",
+                        );
+                        return;
+                    }
+                    ImportStatus::Done => format!("Imported {} cards.", prog.total),
+                    ImportStatus::Cancelled => {
+                        "Import cancelled, none of the cards were saved.".to_string()
+                    }
+                    ImportStatus::Failed(err) => {
+                        format!("Import failed, none of the cards were saved: {}", err)
+                    }
+                };
+                draw_message(f, area, &format!("{}\n\npress enter to continue", msg));
             }
         }
     }
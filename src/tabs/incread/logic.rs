@@ -1,5 +1,7 @@
 use crate::app::AppData;
 use crate::app::Tab;
+use crate::error::Result;
+use crate::utils::sql::delete::remove_source;
 use crate::utils::sql::update::update_inc_text;
 use crate::Direction;
 use crate::MyKey;
@@ -7,6 +9,7 @@ use crate::MyType;
 
 use crate::utils::aliases::*;
 use crate::utils::statelist::StatefulList;
+use crate::widgets::search::Target;
 use crate::widgets::topics::TopicList;
 use tui::layout::Rect;
 use tui::Frame;
@@ -56,64 +59,93 @@ use crate::utils::sql::insert::new_incread;
 use rusqlite::Connection;
 
 impl MainInc {
-    pub fn new(conn: &Arc<Mutex<Connection>>) -> Self {
-        let items = load_inc_items(conn, 1).unwrap();
+    pub fn new(conn: &Arc<Mutex<Connection>>) -> Result<Self> {
+        let items = load_inc_items(conn, 1)?;
         let inclist = StatefulList::with_items(items);
         let mut topics = TopicList::new(conn);
         topics.next();
         let focused: Option<IncRead> = None;
         let menu = Menu::Main;
-        MainInc {
+        Ok(MainInc {
             inclist,
             focused,
             selection: Selection::List,
             extracts: StatefulList::<IncListItem>::new(),
             topics,
             menu,
-        }
+        })
     }
 
-    pub fn update_text(&self, conn: &Arc<Mutex<Connection>>) {
+    pub fn update_text(&self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
         if let Some(inc) = &self.focused {
             let id = inc.id;
             let text = inc.source.return_text();
-            update_inc_text(conn, text, id, &inc.source.cursor).unwrap();
+            update_inc_text(conn, text, id, &inc.source.cursor)?;
         }
+        Ok(())
     }
 
-    pub fn create_source(&mut self, conn: &Arc<Mutex<Connection>>, text: String) {
+    pub fn create_source(&mut self, conn: &Arc<Mutex<Connection>>, text: String) -> Result<()> {
         let topic: TopicID = self.topics.get_selected_id().unwrap();
-        new_incread(conn, 0, topic, text, true).unwrap();
-        self.reload_inc_list(conn);
+        new_incread(conn, 0, topic, text, true)?;
+        self.reload_inc_list(conn)
     }
 
-    pub fn new_focus(&mut self, conn: &Arc<Mutex<Connection>>) {
+    pub fn new_focus(&mut self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
         if let Selection::List = self.selection {
             if let Some(idx) = self.inclist.state.selected() {
                 let id: IncID = self.inclist.items[idx].id;
-                let incread = get_incread(conn, id).unwrap();
+                let incread = get_incread(conn, id)?;
                 self.focused = Some(incread);
-                self.extracts.items = load_extracts(conn, id).unwrap();
+                self.extracts.items = load_extracts(conn, id)?;
             }
         } else if let Selection::Extracts = self.selection {
             if let Some(idx) = self.extracts.state.selected() {
                 let id: IncID = self.extracts.items[idx].id;
-                self.focused = Some(get_incread(conn, id).unwrap());
-                self.extracts.items = load_extracts(conn, id).unwrap();
+                self.focused = Some(get_incread(conn, id)?);
+                self.extracts.items = load_extracts(conn, id)?;
             }
         }
+        Ok(())
     }
 
-    pub fn reload_inc_list(&mut self, conn: &Arc<Mutex<Connection>>) {
-        let items = load_inc_items(conn, self.topics.get_selected_id().unwrap()).unwrap();
+    pub fn reload_inc_list(&mut self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
+        let items = load_inc_items(conn, self.topics.get_selected_id().unwrap())?;
         self.inclist = StatefulList::with_items(items);
+        Ok(())
     }
 
-    pub fn reload_extracts(&mut self, conn: &Arc<Mutex<Connection>>, id: IncID) {
-        self.extracts.items = load_extracts(conn, id).unwrap();
+    pub fn reload_extracts(&mut self, conn: &Arc<Mutex<Connection>>, id: IncID) -> Result<()> {
+        self.extracts.items = load_extracts(conn, id)?;
+        Ok(())
     }
 
-    fn nav_inc(&mut self, conn: &Arc<Mutex<Connection>>, dir: &Direction) {
+    /// Moves the selected text or extract to the trash.
+    fn delete_selected(&mut self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
+        let list = match self.selection {
+            Selection::List => &self.inclist,
+            Selection::Extracts => &self.extracts,
+            _ => return Ok(()),
+        };
+        let id = match list.state.selected() {
+            Some(idx) => list.items[idx].id,
+            None => return Ok(()),
+        };
+        self.update_text(conn)?;
+        remove_source(conn, id)?;
+        if self.focused.as_ref().map(|inc| inc.id) == Some(id) {
+            self.focused = None;
+        }
+        self.reload_inc_list(conn)?;
+        self.extracts = StatefulList::new();
+        if let Some(inc) = &self.focused {
+            let id = inc.id;
+            self.reload_extracts(conn, id)?;
+        }
+        Ok(())
+    }
+
+    fn nav_inc(&mut self, conn: &Arc<Mutex<Connection>>, dir: &Direction) -> Result<()> {
         use crate::Direction::*;
         use Selection::*;
 
@@ -121,9 +153,9 @@ impl MainInc {
 
         match (&self.selection, dir) {
             (Incread, Right) => {
-                self.update_text(conn);
+                self.update_text(conn)?;
                 self.selection = Topics;
-                self.reload_inc_list(conn);
+                self.reload_inc_list(conn)?;
             }
             (Topics, Down) => self.selection = List,
             (List, Up) => self.selection = Topics,
@@ -132,6 +164,7 @@ impl MainInc {
             (_, Left) if focused => self.selection = Incread,
             _ => {}
         }
+        Ok(())
     }
 }
 
@@ -140,6 +173,19 @@ impl Tab for MainInc {
         "Incremental reading".to_string()
     }
 
+    fn jump_to(&mut self, appdata: &mut AppData, target: Target) -> Result<bool> {
+        let id = match target {
+            Target::Source(id) => id,
+            Target::Card(_) => return Ok(false),
+        };
+        self.update_text(&appdata.conn)?;
+        self.menu = Menu::Main;
+        self.focused = Some(get_incread(&appdata.conn, id)?);
+        self.reload_extracts(&appdata.conn, id)?;
+        self.selection = Selection::Incread;
+        Ok(true)
+    }
+
     fn get_manual(&self) -> String {
         r#"
 
@@ -149,6 +195,7 @@ You can paste text into the textwidget.
 
 Add wikipedia page: Alt+w
 add new source: Alt+a
+move selected source or extract to the trash: Ctrl+Delete
 insert mode -> normal mode: Ctrl+c
 normal mode -> insert mode: i
 normal mode -> visual mode: v
@@ -160,15 +207,13 @@ make cloze (visual mode): Alt+z
         .to_string()
     }
 
-    fn keyhandler(&mut self, appdata: &AppData, key: MyKey) {
+    fn keyhandler(&mut self, appdata: &mut AppData, key: MyKey) -> Result<()> {
         use crate::MyKey::*;
         use Selection::*;
         if let MyKey::Nav(dir) = &key {
-            self.nav_inc(&appdata.conn, dir);
-            return;
+            return self.nav_inc(&appdata.conn, dir);
         } else if let MyKey::Alt('a') = &key {
-            self.create_source(&appdata.conn, "".to_string());
-            return;
+            return self.create_source(&appdata.conn, "".to_string());
         } else if let Menu::WikiSelect(wiki) = &mut self.menu {
             match key {
                 Esc => self.menu = Menu::Main,
@@ -177,32 +222,33 @@ make cloze (visual mode): Alt+z
                     let wiki = wikipedia::Wikipedia::<wikipedia::http::default::Client>::default();
                     let page = wiki.page_from_title(text);
                     if let Ok(content) = page.get_content() {
-                        self.create_source(&appdata.conn, content);
+                        self.create_source(&appdata.conn, content)?;
                         self.menu = Menu::Main;
                     }
                 }
                 key => wiki.searchbar.keyhandler(key),
             }
-            return;
+            return Ok(());
         }
 
         match (&self.selection, key) {
-            (Extracts, Enter) => self.new_focus(&appdata.conn),
+            (Extracts, Enter) => self.new_focus(&appdata.conn)?,
             (Extracts, Char('k')) | (Extracts, Up) => self.extracts.previous(),
             (Extracts, Char('j')) | (Extracts, Down) => self.extracts.next(),
             (Topics, key) => {
-                self.topics.keyhandler(key, &appdata.conn);
-                self.reload_inc_list(&appdata.conn);
+                self.topics.keyhandler(key, &appdata.conn)?;
+                self.reload_inc_list(&appdata.conn)?;
             }
-            (List, Enter) => self.new_focus(&appdata.conn),
+            (List, Enter) => self.new_focus(&appdata.conn)?,
+            (List, DeleteCard) | (Extracts, DeleteCard) => self.delete_selected(&appdata.conn)?,
             (List, Char('k')) | (List, Up) => self.inclist.previous(),
             (List, Char('j')) | (List, Down) => self.inclist.next(),
             (Incread, key) => {
                 if let Some(focused) = &mut self.focused {
                     let incid = focused.id;
-                    focused.keyhandler(&appdata.conn, key.clone());
+                    focused.keyhandler(&appdata.conn, key.clone())?;
                     if let MyKey::Alt('x') = &key {
-                        self.reload_extracts(&appdata.conn, incid)
+                        self.reload_extracts(&appdata.conn, incid)?;
                     }
                 }
             }
@@ -212,6 +258,7 @@ make cloze (visual mode): Alt+z
             }
             (_, _) => {}
         }
+        Ok(())
     }
 
     fn render(&mut self, f: &mut Frame<MyType>, _appdata: &AppData, area: Rect) {
@@ -3,3 +3,4 @@ pub mod add_card;
 pub mod import;
 pub mod incread;
 pub mod review;
+pub mod tools;
@@ -1,27 +1,36 @@
-use crate::app::{AppData, Audio};
+use crate::app::{AppData, Audio, Config, FailedDependency};
+use crate::error::Result;
 use crate::utils::aliases::*;
 use crate::utils::incread::IncRead;
-use crate::utils::misc::{get_dependencies, get_dependents};
+use crate::utils::leech::check_leech;
+use crate::utils::limits::{DailyLimits, Limit};
+use crate::utils::order::ReviewOrder;
+use crate::utils::siblings::SiblingBury;
+use crate::utils::misc::{get_dependencies, get_dependents, today_start, unix_now};
 use crate::utils::sql::update::update_inc_active;
 use crate::widgets::cardrater::CardRater;
+use crate::widgets::message_box::draw_message;
 use crate::widgets::textinput::Field;
 use crate::widgets::{
     find_card::{CardPurpose, FindCardWidget},
     mode_status::mode_status,
     newchild::{AddChildWidget, Purpose},
     progress_bar::progress_bar,
+    search::Target,
+    tag_editor::TagEditor,
     textinput::CursorPos,
 };
 use crate::{
     app::Tab,
     utils::{
-        card::{Card, CardType, RecallGrade},
+        card::{Card, CardState, CardType, RecallGrade, Review},
         misc::{centered_rect, modecolor, PopUpStatus},
         sql::{
-            fetch::get_cardtype,
+            delete::{remove_card, remove_revlog, remove_source},
+            fetch::{get_card_state, get_cardtype, get_stability, get_topic_of_card},
             update::{
-                double_inc_skip_duration, double_skip_duration, update_card_answer,
-                update_card_question, update_inc_text,
+                double_inc_skip_duration, double_skip_duration, restore_card_state, set_stability,
+                set_suspended, update_card_answer, update_card_question, update_inc_text,
             },
         },
     },
@@ -30,6 +39,7 @@ use crate::{
 use rand::prelude::*;
 use rusqlite::Connection;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tui::{
     layout::{Constraint, Direction, Layout, Rect},
     widgets::Clear,
@@ -39,6 +49,8 @@ use tui::{
 pub enum ReviewMode {
     Review(CardReview),
     Pending(CardReview),
+    /// A failed or new card on its way through the learning steps, with the step it's on.
+    Learning(CardReview, usize),
     Unfinished(UnfCard),
     IncRead(IncMode),
     Done,
@@ -49,40 +61,75 @@ pub struct ForReview {
     pub unfinished_cards: Vec<CardID>,
     pub pending_cards: Vec<CardID>,
     pub active_increads: Vec<IncID>,
+    pub learning: Vec<LearnCard>,
+}
+
+/// A card that comes back later in the session.
+pub struct LearnCard {
+    pub id: CardID,
+    pub step: usize,
+    pub due: u32,
 }
 
 impl ForReview {
-    pub fn new(conn: &Arc<Mutex<Connection>>) -> Self {
-        crate::utils::interval::calc_strength(conn);
+    pub fn new(
+        conn: &Arc<Mutex<Connection>>,
+        config: &Config,
+        limits: &DailyLimits,
+        order: ReviewOrder,
+    ) -> Result<Self> {
+        crate::utils::interval::calc_strength(conn, &*config.scheduler())?;
 
+        let topics = get_topics(conn)?;
         let mut review_cards = CardQuery::default()
-            .strength((0., 0.9))
+            .below_retention(config.retention_overrides(&topics), config.retention)
             .suspended(false)
             .resolved(true)
-            .fetch_card_ids(conn);
+            .fetch_card_ids(conn)?;
         let mut unfinished_cards = CardQuery::default()
             .unfinished_due()
             .suspended(false)
             .resolved(true)
-            .fetch_card_ids(conn);
+            .fetch_card_ids(conn)?;
         let mut pending_cards = CardQuery::default()
             .cardtype(CardType::Pending)
             .suspended(false)
             .resolved(true)
-            .fetch_card_ids(conn);
+            .fetch_card_ids(conn)?;
 
-        let active_increads = load_active_inc(conn).unwrap();
+        let active_increads = load_active_inc(conn)?;
 
-        unfinished_cards.shuffle(&mut thread_rng());
-        pending_cards.shuffle(&mut thread_rng());
-        review_cards.shuffle(&mut thread_rng());
+        order.sort(conn, config, &mut unfinished_cards)?;
+        order.sort(conn, config, &mut pending_cards)?;
+        order.sort(conn, config, &mut review_cards)?;
+
+        if config.bury_siblings {
+            let mut bury = SiblingBury::new(conn)?;
+            review_cards = bury.bury(conn, review_cards)?;
+            pending_cards = bury.bury(conn, pending_cards)?;
+        }
 
-        ForReview {
+        let review_cards = limits.select(conn, Limit::Reviews, review_cards)?;
+        let pending_cards = limits.select(conn, Limit::New, pending_cards)?;
+
+        Ok(ForReview {
             review_cards,
             unfinished_cards,
             pending_cards,
             active_increads,
-        }
+            learning: vec![],
+        })
+    }
+
+    pub fn sort(
+        &mut self,
+        conn: &Arc<Mutex<Connection>>,
+        config: &Config,
+        order: ReviewOrder,
+    ) -> Result<()> {
+        order.sort(conn, config, &mut self.review_cards)?;
+        order.sort(conn, config, &mut self.unfinished_cards)?;
+        order.sort(conn, config, &mut self.pending_cards)
     }
 }
 
@@ -91,6 +138,7 @@ pub struct StartQty {
     pub unf_qty: u16,
     pub pending_qty: u16,
     pub inc_qty: u16,
+    pub learn_qty: u16,
 }
 
 impl StartQty {
@@ -105,12 +153,35 @@ impl StartQty {
             unf_qty,
             pending_qty,
             inc_qty,
+            learn_qty: 0,
         }
     }
 }
 pub enum PopUp {
     CardSelecter(FindCardWidget),
     AddChild(AddChildWidget),
+    Tags(TagEditor),
+    Message(String),
+}
+
+/// The queue a card was taken from, so an undo can put it back.
+#[derive(Clone, Copy)]
+pub enum Queue {
+    Review,
+    Pending,
+    Unfinished,
+    Learning(usize),
+}
+
+/// What a review, suspend or completion changed, so it can be taken back.
+pub struct Undo {
+    pub id: CardID,
+    pub state: CardState,
+    pub dependents: Vec<(CardID, CardState)>,
+    pub revlog: Option<RevlogID>,
+    pub requeued: Vec<CardID>,
+    pub queue: Queue,
+    pub limit: Option<(Limit, TopicID)>,
 }
 
 pub struct MainReview {
@@ -120,14 +191,25 @@ pub struct MainReview {
     pub start_qty: StartQty,
     pub automode: bool,
     pub popup: Option<PopUp>,
+    pub limits: DailyLimits,
+    pub undo: Vec<Undo>,
+    pub order: ReviewOrder,
 }
 
-use crate::utils::sql::fetch::{fetch_card, fetch_media, load_active_inc, CardQuery};
+use crate::utils::sql::fetch::{
+    cards_reviewed_since, fetch_card, fetch_media, get_topics, load_active_inc, CardQuery,
+};
 
 impl MainReview {
-    pub fn new(conn: &Arc<Mutex<Connection>>, audio: &Option<Audio>) -> Self {
+    pub fn new(
+        conn: &Arc<Mutex<Connection>>,
+        audio: &Option<Audio>,
+        config: &Config,
+    ) -> Result<Self> {
         let mode = ReviewMode::Done;
-        let for_review = ForReview::new(conn);
+        let limits = DailyLimits::new(conn, config)?;
+        let order = config.review_order;
+        let for_review = ForReview::new(conn, config, &limits, order)?;
         let start_qty = StartQty::new(&for_review);
 
         let mut myself = Self {
@@ -137,36 +219,46 @@ impl MainReview {
             start_qty,
             automode: true,
             popup: None,
+            limits,
+            undo: vec![],
+            order,
         };
-        myself.random_mode(conn, audio);
-        myself
+        myself.random_mode(conn, audio)?;
+        Ok(myself)
     }
 
-    fn update_dependencies(&mut self, conn: &Arc<Mutex<Connection>>) {
+    fn update_dependencies(&mut self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
         match &mut self.mode {
             ReviewMode::Review(rev) => {
-                rev.dependencies = get_dependencies(conn, rev.id);
-                rev.dependents = get_dependents(conn, rev.id);
+                rev.dependencies = get_dependencies(conn, rev.id)?;
+                rev.dependents = get_dependents(conn, rev.id)?;
             }
             ReviewMode::Unfinished(rev) => {
-                rev.dependencies = get_dependencies(conn, rev.id);
-                rev.dependents = get_dependents(conn, rev.id);
+                rev.dependencies = get_dependencies(conn, rev.id)?;
+                rev.dependents = get_dependents(conn, rev.id)?;
             }
-            ReviewMode::Pending(rev) => {
-                rev.dependencies = get_dependencies(conn, rev.id);
-                rev.dependents = get_dependents(conn, rev.id);
+            ReviewMode::Pending(rev) | ReviewMode::Learning(rev, _) => {
+                rev.dependencies = get_dependencies(conn, rev.id)?;
+                rev.dependents = get_dependents(conn, rev.id)?;
             }
             _ => {}
         }
+        Ok(())
     }
 
-    // randomly choose a mode between active, unfinished and inc read, if theyre all done,
-    // start with pending cards, if theyre all done, declare nothing left to review
+    // learning cards come first once they're due. otherwise randomly choose a mode between
+    // active, unfinished and inc read, or go through them one after the other if the session
+    // isn't in random order. if theyre all done, start with pending cards, and then the
+    // learning cards that aren't due yet, before declaring nothing left to review
     pub fn random_mode(
         &mut self,
         conn: &Arc<Mutex<Connection>>,
         audio: &Option<Audio>
-    ) {
+    ) -> Result<()> {
+        if let Some(idx) = self.next_learning(Some(unix_now())) {
+            return self.new_learning_mode(conn, audio, idx);
+        }
+
         let act: u32 = self.for_review.review_cards.len() as u32;
         let unf: u32 = self.for_review.unfinished_cards.len() as u32 + act;
         let inc: u32 = self.for_review.active_increads.len() as u32 + unf;
@@ -174,31 +266,32 @@ impl MainReview {
         let pending_qty = self.for_review.pending_cards.len() as u32;
         if inc == 0 {
             if pending_qty > 0 {
-                self.new_pending_mode(conn, audio);
-            } else {
-                self.mode = ReviewMode::Done;
+                return self.new_pending_mode(conn, audio);
+            } else if let Some(idx) = self.next_learning(None) {
+                return self.new_learning_mode(conn, audio, idx);
             }
-            return;
+            self.mode = ReviewMode::Done;
+            return Ok(());
         }
 
-        let mut rng = rand::thread_rng();
-        let rand = rng.gen_range(0..inc);
+        let rand = match self.order {
+            ReviewOrder::Random => rand::thread_rng().gen_range(0..inc),
+            _ => 0,
+        };
 
         if rand < act {
-            self.new_review_mode(conn, audio);
+            self.new_review_mode(conn, audio)
         } else if rand < unf {
-            self.new_unfinished_mode(conn, audio);
-        } else if rand < inc {
-            self.new_inc_mode(conn);
+            self.new_unfinished_mode(conn, audio)
         } else {
-            panic!();
-        };
+            self.new_inc_mode(conn)
+        }
     }
 
-    pub fn new_inc_mode(&mut self, conn: &Arc<Mutex<Connection>>) {
+    pub fn new_inc_mode(&mut self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
         let id = self.for_review.active_increads.remove(0);
         let selection = IncSelection::Source;
-        let source = IncRead::new(conn, id);
+        let source = IncRead::new(conn, id)?;
         let inc = IncMode {
             id,
             source,
@@ -206,22 +299,23 @@ impl MainReview {
         };
 
         self.mode = ReviewMode::IncRead(inc);
+        Ok(())
     }
     pub fn new_unfinished_mode(
         &mut self,
         conn: &Arc<Mutex<Connection>>,
         audio: &Option<Audio>
-    ) {
+    ) -> Result<()> {
         let id = self.for_review.unfinished_cards.remove(0);
-        Card::play_frontaudio(conn, id, audio);
+        Card::play_frontaudio(conn, id, audio)?;
         let selection = UnfSelection::Question;
         let mut question = Field::new();
         let mut answer = Field::new();
-        let card = fetch_card(&conn, id);
+        let card = fetch_card(conn, id)?;
         question.replace_text(card.question);
         answer.replace_text(card.answer);
-        let dependencies = get_dependencies(conn, id);
-        let dependents = get_dependents(conn, id);
+        let dependencies = get_dependencies(conn, id)?;
+        let dependents = get_dependents(conn, id)?;
         let unfcard = UnfCard {
             id,
             question,
@@ -231,27 +325,27 @@ impl MainReview {
             selection,
         };
         self.mode = ReviewMode::Unfinished(unfcard);
+        Ok(())
     }
 
-    pub fn new_pending_mode(
-        &mut self,
+    fn new_card_review(
         conn: &Arc<Mutex<Connection>>,
-        audio: &Option<Audio>
-    ) {
-        let id = self.for_review.pending_cards.remove(0);
-        Card::play_frontaudio(conn, id, audio);
+        audio: &Option<Audio>,
+        id: CardID,
+    ) -> Result<CardReview> {
+        Card::play_frontaudio(conn, id, audio)?;
         let reveal = false;
         let selection = ReviewSelection::RevealButton;
         let mut question = Field::new();
         let mut answer = Field::new();
-        let card = fetch_card(&conn, id);
+        let card = fetch_card(conn, id)?;
         question.replace_text(card.question);
         answer.replace_text(card.answer);
-        let dependencies = get_dependencies(conn, id);
-        let dependents = get_dependents(conn, id);
+        let dependencies = get_dependencies(conn, id)?;
+        let dependents = get_dependents(conn, id)?;
         let cardrater = CardRater::new();
-        let media = fetch_media(&conn, id);
-        let cardreview = CardReview {
+        let media = fetch_media(conn, id)?;
+        Ok(CardReview {
             id,
             question,
             answer,
@@ -261,41 +355,82 @@ impl MainReview {
             selection,
             cardrater,
             media,
-        };
+            shown: Instant::now(),
+            revealed: None,
+        })
+    }
 
+    pub fn new_pending_mode(
+        &mut self,
+        conn: &Arc<Mutex<Connection>>,
+        audio: &Option<Audio>
+    ) -> Result<()> {
+        let id = self.for_review.pending_cards.remove(0);
+        let cardreview = Self::new_card_review(conn, audio, id)?;
         self.mode = ReviewMode::Pending(cardreview);
+        Ok(())
     }
     pub fn new_review_mode(
         &mut self,
         conn: &Arc<Mutex<Connection>>,
         audio: &Option<Audio>
-    ) {
+    ) -> Result<()> {
         let id = self.for_review.review_cards.remove(0);
-        Card::play_frontaudio(conn, id, audio);
-        let reveal = false;
-        let selection = ReviewSelection::RevealButton;
-        let mut question = Field::new();
-        let mut answer = Field::new();
-        let card = fetch_card(conn, id);
-        question.replace_text(card.question);
-        answer.replace_text(card.answer);
-        let dependencies = get_dependencies(conn, id);
-        let dependents = get_dependents(conn, id);
-        let cardrater = CardRater::new();
-        let media = fetch_media(&conn, id);
-        let cardreview = CardReview {
-            id,
-            question,
-            dependencies,
-            dependents,
-            answer,
-            reveal,
-            selection,
-            cardrater,
-            media,
-        };
-
+        let cardreview = Self::new_card_review(conn, audio, id)?;
         self.mode = ReviewMode::Review(cardreview);
+        Ok(())
+    }
+    pub fn new_learning_mode(
+        &mut self,
+        conn: &Arc<Mutex<Connection>>,
+        audio: &Option<Audio>,
+        idx: usize,
+    ) -> Result<()> {
+        let learncard = self.for_review.learning.remove(idx);
+        let cardreview = Self::new_card_review(conn, audio, learncard.id)?;
+        self.mode = ReviewMode::Learning(cardreview, learncard.step);
+        Ok(())
+    }
+
+    /// The learning card that's been waiting the longest, only if it's due by `now`.
+    fn next_learning(&self, now: Option<u32>) -> Option<usize> {
+        self.for_review
+            .learning
+            .iter()
+            .enumerate()
+            .filter(|(_, card)| match now {
+                Some(now) => card.due <= now,
+                None => true,
+            })
+            .min_by_key(|(_, card)| card.due)
+            .map(|(idx, _)| idx)
+    }
+
+    // failed and new cards come back after each learning step until they've passed the
+    // last one, a failure starts them over
+    fn learn(
+        &mut self,
+        conn: &Arc<Mutex<Connection>>,
+        id: CardID,
+        step: Option<usize>,
+        grade: &RecallGrade,
+        steps: &[f32],
+    ) -> Result<()> {
+        let card = fetch_card(conn, id)?;
+        if steps.is_empty() || card.cardtype != CardType::Finished || card.suspended {
+            return Ok(());
+        }
+        let next = match grade {
+            RecallGrade::None | RecallGrade::Failed => Some(0),
+            RecallGrade::Decent => step.map(|step| step + 1).filter(|step| *step < steps.len()),
+            RecallGrade::Easy => None,
+        };
+        if let Some(step) = next {
+            let due = unix_now() + (steps[step] * 60.) as u32;
+            self.for_review.learning.push(LearnCard { id, step, due });
+            self.start_qty.learn_qty += 1;
+        }
+        Ok(())
     }
 
     pub fn inc_next(
@@ -303,30 +438,210 @@ impl MainReview {
         conn: &Arc<Mutex<Connection>>,
         audio: &Option<Audio>,
         id: IncID,
-    ) {
-        self.random_mode(conn, audio);
-        double_inc_skip_duration(conn, id).unwrap();
+    ) -> Result<()> {
+        self.random_mode(conn, audio)?;
+        double_inc_skip_duration(conn, id)?;
+        Ok(())
     }
     pub fn inc_done(
         &mut self,
         id: IncID,
         conn: &Arc<Mutex<Connection>>,
         audio: &Option<Audio>
-    ) {
+    ) -> Result<()> {
         let active = false;
-        update_inc_active(&conn, id, active).unwrap();
-        self.random_mode(conn, audio);
+        update_inc_active(conn, id, active)?;
+        self.random_mode(conn, audio)
+    }
+
+    /// Moves the card or text that's currently shown to the trash and goes on with the next.
+    fn delete_current(&mut self, conn: &Arc<Mutex<Connection>>, audio: &Option<Audio>) -> Result<()> {
+        let id = match &self.mode {
+            ReviewMode::Review(rev) | ReviewMode::Pending(rev) | ReviewMode::Learning(rev, _) => {
+                rev.id
+            }
+            ReviewMode::Unfinished(unf) => unf.id,
+            ReviewMode::IncRead(inc) => {
+                remove_source(conn, inc.id)?;
+                return self.random_mode(conn, audio);
+            }
+            ReviewMode::Done => return Ok(()),
+        };
+        remove_card(conn, id)?;
+        // there's no state left to go back to, restoring it from the trash brings it all back
+        self.undo.retain(|undo| undo.id != id);
+        for undo in &mut self.undo {
+            undo.dependents.retain(|(dependent, _)| *dependent != id);
+        }
+        self.for_review.review_cards.retain(|card| *card != id);
+        self.for_review.learning.retain(|card| card.id != id);
+        self.random_mode(conn, audio)
     }
 
     pub fn new_review(
+        &mut self,
+        conn: &Arc<Mutex<Connection>>,
+        undo: &mut Undo,
+        review: Review,
+        audio: &Option<Audio>,
+        config: &Config,
+        step: Option<usize>,
+    ) -> Result<()> {
+        let id = undo.id;
+        let recallgrade = review.grade.clone();
+        let failed = matches!(recallgrade, RecallGrade::None | RecallGrade::Failed);
+        undo.revlog = Some(Card::new_review(conn, id, review, &*config.scheduler())?);
+        if failed {
+            undo.requeued = self.failed_dependency(conn, id, config)?;
+            if let Some(notice) = check_leech(conn, id, config)? {
+                self.popup = Some(PopUp::Message(notice));
+            }
+        }
+        self.learn(conn, id, step, &recallgrade, &config.learning_steps)?;
+        self.random_mode(conn, audio)
+    }
+
+    fn current_queue(&self) -> Option<Queue> {
+        match self.mode {
+            ReviewMode::Review(_) => Some(Queue::Review),
+            ReviewMode::Pending(_) => Some(Queue::Pending),
+            ReviewMode::Unfinished(_) => Some(Queue::Unfinished),
+            ReviewMode::Learning(_, step) => Some(Queue::Learning(step)),
+            _ => None,
+        }
+    }
+
+    fn snapshot(conn: &Arc<Mutex<Connection>>, id: CardID, queue: Queue) -> Result<Undo> {
+        let mut dependents = vec![];
+        for dependent in fetch_card(conn, id)?.dependents {
+            dependents.push((dependent, get_card_state(conn, dependent)?));
+        }
+        Ok(Undo {
+            id,
+            state: get_card_state(conn, id)?,
+            dependents,
+            revlog: None,
+            requeued: vec![],
+            queue,
+            limit: None,
+        })
+    }
+
+    // puts the card that's currently shown back at the front of its queue
+    fn requeue_current(&mut self) {
+        match std::mem::replace(&mut self.mode, ReviewMode::Done) {
+            ReviewMode::Review(rev) => self.for_review.review_cards.insert(0, rev.id),
+            ReviewMode::Pending(rev) => self.for_review.pending_cards.insert(0, rev.id),
+            ReviewMode::Unfinished(unf) => self.for_review.unfinished_cards.insert(0, unf.id),
+            ReviewMode::IncRead(inc) => self.for_review.active_increads.insert(0, inc.id),
+            ReviewMode::Learning(rev, step) => self.for_review.learning.push(LearnCard {
+                id: rev.id,
+                step,
+                due: unix_now(),
+            }),
+            ReviewMode::Done => {}
+        }
+    }
+
+    /// Takes back the last review, suspend or completion and shows that card again.
+    pub fn undo(&mut self, conn: &Arc<Mutex<Connection>>, audio: &Option<Audio>) -> Result<()> {
+        let undo = match self.undo.pop() {
+            Some(undo) => undo,
+            None => return Ok(()),
+        };
+        if let Some(rowid) = undo.revlog {
+            remove_revlog(conn, rowid)?;
+        }
+        restore_card_state(conn, undo.id, &undo.state)?;
+        for (dependent, state) in &undo.dependents {
+            restore_card_state(conn, *dependent, state)?;
+        }
+        for dependent in fetch_card(conn, undo.id)?.dependents {
+            Card::check_resolved(dependent, conn)?;
+        }
+        if let Some((kind, topic)) = undo.limit {
+            self.limits.remove(kind, topic);
+        }
+
+        self.requeue_current();
+        let learning = &mut self.for_review.learning;
+        if let Some(idx) = learning.iter().position(|card| card.id == undo.id) {
+            learning.remove(idx);
+            self.start_qty.learn_qty -= 1;
+        }
+        let review_cards = &mut self.for_review.review_cards;
+        for dependent in &undo.requeued {
+            if let Some(idx) = review_cards.iter().position(|card| card == dependent) {
+                review_cards.remove(idx);
+                self.start_qty.fin_qty -= 1;
+            }
+        }
+
+        match undo.queue {
+            Queue::Review => {
+                self.for_review.review_cards.insert(0, undo.id);
+                self.new_review_mode(conn, audio)
+            }
+            Queue::Pending => {
+                self.for_review.pending_cards.insert(0, undo.id);
+                self.new_pending_mode(conn, audio)
+            }
+            Queue::Unfinished => {
+                self.for_review.unfinished_cards.insert(0, undo.id);
+                self.new_unfinished_mode(conn, audio)
+            }
+            Queue::Learning(step) => {
+                self.for_review.learning.insert(
+                    0,
+                    LearnCard {
+                        id: undo.id,
+                        step,
+                        due: 0,
+                    },
+                );
+                self.new_learning_mode(conn, audio, 0)
+            }
+        }
+    }
+
+    // the dependents were built on knowledge that we just showed was lost, returns the ones
+    // that got put in the review queue
+    fn failed_dependency(
         &mut self,
         conn: &Arc<Mutex<Connection>>,
         id: CardID,
-        recallgrade: RecallGrade,
-        audio: &Option<Audio>
-    ) {
-        Card::new_review(conn, id, recallgrade);
-        self.random_mode(conn, audio);
+        config: &Config,
+    ) -> Result<Vec<CardID>> {
+        let mut requeued = vec![];
+        let reviewed = match config.on_failed_dependency {
+            FailedDependency::Requeue => cards_reviewed_since(conn, today_start())?,
+            _ => vec![],
+        };
+        for dependent in fetch_card(conn, id)?.dependents {
+            let card = fetch_card(conn, dependent)?;
+            if card.cardtype != CardType::Finished || card.suspended || !card.resolved {
+                continue;
+            }
+            match config.on_failed_dependency {
+                FailedDependency::None => {}
+                FailedDependency::Weaken => {
+                    // only brings the card up sooner, its next review replays the history
+                    // without it, as does a recompute
+                    let stability = get_stability(conn, dependent)? * config.dependent_weakening;
+                    set_stability(conn, dependent, stability)?;
+                }
+                FailedDependency::Requeue => {
+                    if !self.for_review.review_cards.contains(&dependent)
+                        && !reviewed.contains(&dependent)
+                    {
+                        self.for_review.review_cards.push(dependent);
+                        self.start_qty.fin_qty += 1;
+                        requeued.push(dependent);
+                    }
+                }
+            }
+        }
+        Ok(requeued)
     }
 
     pub fn draw_progress_bar(&mut self, f: &mut Frame<MyType>, area: Rect) {
@@ -334,6 +649,7 @@ impl MainReview {
             ReviewMode::Done => return,
             ReviewMode::Review(_) => self.start_qty.fin_qty,
             ReviewMode::Pending(_) => self.start_qty.pending_qty,
+            ReviewMode::Learning(..) => self.start_qty.learn_qty,
             ReviewMode::IncRead(_) => self.start_qty.inc_qty,
             ReviewMode::Unfinished(_) => self.start_qty.unf_qty,
         } as u32;
@@ -346,6 +662,9 @@ impl MainReview {
             ReviewMode::Pending(_) => {
                 (self.start_qty.pending_qty as u32) - (self.for_review.pending_cards.len() as u32)
             }
+            ReviewMode::Learning(..) => {
+                (self.start_qty.learn_qty as u32) - (self.for_review.learning.len() as u32)
+            }
             ReviewMode::IncRead(_) => {
                 (self.start_qty.inc_qty as u32) - (self.for_review.active_increads.len() as u32)
             }
@@ -355,7 +674,13 @@ impl MainReview {
         };
 
         let color = modecolor(&self.mode);
-        progress_bar(f, current, target, color, area, "progress");
+        let title = format!(
+            "progress   new today: {}   reviews today: {}   order: {}",
+            self.limits.status(Limit::New),
+            self.limits.status(Limit::Reviews),
+            self.order
+        );
+        progress_bar(f, current, target, color, area, &title);
     }
 }
 
@@ -382,6 +707,7 @@ impl Tab for MainReview {
             ReviewMode::Done => draw_done(f, area),
             ReviewMode::Review(review) => review.render(f, &appdata.conn, area),
             ReviewMode::Pending(pending) => pending.render(f, &appdata.conn, area),
+            ReviewMode::Learning(learning, _) => learning.render(f, &appdata.conn, area),
             ReviewMode::Unfinished(unfinished) => unfinished.render(f, &appdata.conn, area),
             ReviewMode::IncRead(inc) => inc.render(f, &appdata.conn, area),
         }
@@ -401,6 +727,10 @@ impl Tab for MainReview {
                 crate::tabs::review::logic::PopUp::CardSelecter(cardselecter) => {
                     cardselecter.render(f, area)
                 }
+                crate::tabs::review::logic::PopUp::Tags(tags) => tags.render(f, area),
+                crate::tabs::review::logic::PopUp::Message(msg) => {
+                    draw_message(f, area, &format!("{} (press enter to continue)", msg))
+                }
             }
         }
     }
@@ -408,49 +738,102 @@ impl Tab for MainReview {
         "Review".to_string()
     }
 
+    // the card is put in front of its queue, even if it isn't due
+    fn jump_to(&mut self, appdata: &mut AppData, target: Target) -> Result<bool> {
+        let id = match target {
+            Target::Card(id) => id,
+            Target::Source(_) => return Ok(false),
+        };
+        self.popup = None;
+        self.requeue_current();
+        let cardtype = get_cardtype(&appdata.conn, id)?;
+        let (queue, qty) = match cardtype {
+            CardType::Finished => (&mut self.for_review.review_cards, &mut self.start_qty.fin_qty),
+            CardType::Unfinished => (
+                &mut self.for_review.unfinished_cards,
+                &mut self.start_qty.unf_qty,
+            ),
+            CardType::Pending => (
+                &mut self.for_review.pending_cards,
+                &mut self.start_qty.pending_qty,
+            ),
+        };
+        match queue.iter().position(|queued| *queued == id) {
+            Some(idx) => {
+                queue.remove(idx);
+            }
+            None => *qty += 1,
+        }
+        queue.insert(0, id);
+        match cardtype {
+            CardType::Finished => self.new_review_mode(&appdata.conn, &appdata.audio)?,
+            CardType::Unfinished => self.new_unfinished_mode(&appdata.conn, &appdata.audio)?,
+            CardType::Pending => self.new_pending_mode(&appdata.conn, &appdata.audio)?,
+        }
+        Ok(true)
+    }
+
     fn get_manual(&self) -> String {
         match &self.mode {
             ReviewMode::Done => "".to_string(),
             ReviewMode::Review(rev) => rev.get_manual(),
-            ReviewMode::Pending(rev) => rev.get_manual(),
+            ReviewMode::Pending(rev) | ReviewMode::Learning(rev, _) => rev.get_manual(),
             ReviewMode::IncRead(inc) => inc.get_manual(),
             ReviewMode::Unfinished(unf) => unf.get_manual(),
         }
     }
 
-    fn keyhandler(&mut self, appdata: &AppData, key: MyKey) {
+    fn keyhandler(&mut self, appdata: &mut AppData, key: MyKey) -> Result<()> {
         let mut action = Action::None;
         if let Some(popup) = &mut self.popup {
             let wtf = match popup {
                 PopUp::CardSelecter(findcardwidget) => {
-                    findcardwidget.keyhandler(&appdata.conn, key)
+                    findcardwidget.keyhandler(&appdata.conn, key)?
                 }
-                PopUp::AddChild(addchildwidget) => addchildwidget.keyhandler(&appdata.conn, key),
+                PopUp::AddChild(addchildwidget) => addchildwidget.keyhandler(&appdata.conn, key)?,
+                PopUp::Tags(tags) => tags.keyhandler(&appdata.conn, key)?,
+                PopUp::Message(_) => match key {
+                    MyKey::Enter | MyKey::Esc | MyKey::Char(' ') => PopUpStatus::Finished,
+                    _ => PopUpStatus::OnGoing,
+                },
             };
             if let PopUpStatus::Finished = wtf {
                 self.popup = None;
-                self.update_dependencies(&appdata.conn);
+                self.update_dependencies(&appdata.conn)?;
             };
-            return;
+            return Ok(());
+        }
+
+        if let MyKey::Ctrl('z') = key {
+            return self.undo(&appdata.conn, &appdata.audio);
+        }
+        if let MyKey::DeleteCard = key {
+            return self.delete_current(&appdata.conn, &appdata.audio);
+        }
+        if let MyKey::Alt('o') = key {
+            self.order = self.order.next();
+            return self
+                .for_review
+                .sort(&appdata.conn, &appdata.config, self.order);
         }
 
         match &mut self.mode {
             ReviewMode::Done => mode_done(key, &mut action),
-            ReviewMode::Unfinished(unf) => unf.keyhandler(appdata, key, &mut action),
-            ReviewMode::Pending(rev) | ReviewMode::Review(rev) => {
-                rev.keyhandler(&appdata.conn, key, &mut action)
+            ReviewMode::Unfinished(unf) => unf.keyhandler(appdata, key, &mut action)?,
+            ReviewMode::Pending(rev) | ReviewMode::Review(rev) | ReviewMode::Learning(rev, _) => {
+                rev.keyhandler(key, &mut action)
             }
-            ReviewMode::IncRead(inc) => inc.keyhandler(&appdata.conn, key, &mut action),
+            ReviewMode::IncRead(inc) => inc.keyhandler(&appdata.conn, key, &mut action)?,
         }
 
         match action {
             Action::IncNext(source, id, cursor) => {
-                self.inc_next(&appdata.conn, &appdata.audio, id);
-                update_inc_text(&appdata.conn, source, id, &cursor).unwrap();
+                self.inc_next(&appdata.conn, &appdata.audio, id)?;
+                update_inc_text(&appdata.conn, source, id, &cursor)?;
             }
             Action::IncDone(source, id, cursor) => {
-                self.inc_done(id, &appdata.conn, &appdata.audio);
-                update_inc_text(&appdata.conn, source, id, &cursor).unwrap();
+                self.inc_done(id, &appdata.conn, &appdata.audio)?;
+                update_inc_text(&appdata.conn, source, id, &cursor)?;
             }
             Action::Review(question, answer, id, char) => {
                 let grade = match char {
@@ -460,68 +843,116 @@ impl Tab for MainReview {
                     '4' => RecallGrade::Easy,
                     _ => panic!("illegal argument"),
                 };
-                if get_cardtype(&appdata.conn, id) == CardType::Pending {
-                    Card::activate_card(&appdata.conn, id);
+                // a new card's first showing counts as its first learning step
+                let (step, times) = match &self.mode {
+                    ReviewMode::Learning(rev, step) => (Some(*step), rev.times()),
+                    ReviewMode::Pending(rev) => (Some(0), rev.times()),
+                    ReviewMode::Review(rev) => (None, rev.times()),
+                    _ => (None, (-1., -1.)),
+                };
+                let review = Review::from(&grade).with_times(times.0, times.1);
+                let topic = get_topic_of_card(&appdata.conn, id)?;
+                let queue = self.current_queue().unwrap_or(Queue::Review);
+                let mut undo = Self::snapshot(&appdata.conn, id, queue)?;
+                if get_cardtype(&appdata.conn, id)? == CardType::Pending {
+                    Card::activate_card(&appdata.conn, id)?;
+                    self.limits.add(Limit::New, topic);
+                    undo.limit = Some((Limit::New, topic));
+                } else if !matches!(self.mode, ReviewMode::Learning(..)) {
+                    self.limits.add(Limit::Reviews, topic);
+                    undo.limit = Some((Limit::Reviews, topic));
                 }
-                self.new_review(&appdata.conn, id, grade, &appdata.audio);
-                update_card_question(&appdata.conn, id, question).unwrap();
-                update_card_answer(&appdata.conn, id, answer).unwrap();
+                self.new_review(
+                    &appdata.conn,
+                    &mut undo,
+                    review,
+                    &appdata.audio,
+                    &appdata.config,
+                    step,
+                )?;
+                self.undo.push(undo);
+                update_card_question(&appdata.conn, id, question)?;
+                update_card_answer(&appdata.conn, id, answer)?;
             }
             Action::SkipUnf(question, answer, id) => {
-                self.random_mode(&appdata.conn, &appdata.audio);
-                update_card_question(&appdata.conn, id, question).unwrap();
-                update_card_answer(&appdata.conn, id, answer).unwrap();
-                double_skip_duration(&appdata.conn, id).unwrap();
+                self.random_mode(&appdata.conn, &appdata.audio)?;
+                update_card_question(&appdata.conn, id, question)?;
+                update_card_answer(&appdata.conn, id, answer)?;
+                double_skip_duration(&appdata.conn, id)?;
             }
             Action::SkipRev(question, answer, id) => {
-                self.random_mode(&appdata.conn, &appdata.audio);
-                update_card_question(&appdata.conn, id, question).unwrap();
-                update_card_answer(&appdata.conn, id, answer).unwrap();
+                self.random_mode(&appdata.conn, &appdata.audio)?;
+                update_card_question(&appdata.conn, id, question)?;
+                update_card_answer(&appdata.conn, id, answer)?;
+            }
+            Action::Suspend(question, answer, id) => {
+                if let Some(queue) = self.current_queue() {
+                    self.undo.push(Self::snapshot(&appdata.conn, id, queue)?);
+                }
+                set_suspended(&appdata.conn, id, true)?;
+                self.random_mode(&appdata.conn, &appdata.audio)?;
+                update_card_question(&appdata.conn, id, question)?;
+                update_card_answer(&appdata.conn, id, answer)?;
             }
             Action::CompleteUnf(question, answer, id) => {
-                Card::complete_card(&appdata.conn, id);
-                self.random_mode(&appdata.conn, &appdata.audio);
-                update_card_question(&appdata.conn, id, question).unwrap();
-                update_card_answer(&appdata.conn, id, answer).unwrap();
+                let mut undo = Self::snapshot(&appdata.conn, id, Queue::Unfinished)?;
+                undo.revlog = Some(Card::complete_card(&appdata.conn, id)?);
+                self.undo.push(undo);
+                self.random_mode(&appdata.conn, &appdata.audio)?;
+                update_card_question(&appdata.conn, id, question)?;
+                update_card_answer(&appdata.conn, id, answer)?;
             }
             Action::NewDependency(id) => {
                 let prompt = String::from("Add new dependency");
                 let purpose = CardPurpose::NewDependency(id);
-                let cardfinder = FindCardWidget::new(&appdata.conn, prompt, purpose);
+                let cardfinder = FindCardWidget::new(&appdata.conn, prompt, purpose)?;
                 self.popup = Some(PopUp::CardSelecter(cardfinder));
             }
             Action::NewDependent(id) => {
                 let prompt = String::from("Add new dependent");
                 let purpose = CardPurpose::NewDependent(id);
-                let cardfinder = FindCardWidget::new(&appdata.conn, prompt, purpose);
+                let cardfinder = FindCardWidget::new(&appdata.conn, prompt, purpose)?;
                 self.popup = Some(PopUp::CardSelecter(cardfinder));
             }
             Action::AddDependent(id) => {
-                let addchild = AddChildWidget::new(&appdata.conn, Purpose::Dependency(id));
+                let addchild = AddChildWidget::new(&appdata.conn, Purpose::Dependency(id))?;
                 self.popup = Some(PopUp::AddChild(addchild));
             }
             Action::AddDependency(id) => {
-                let addchild = AddChildWidget::new(&appdata.conn, Purpose::Dependent(id));
+                let addchild = AddChildWidget::new(&appdata.conn, Purpose::Dependent(id))?;
                 self.popup = Some(PopUp::AddChild(addchild));
             }
             Action::AddChild(id) => {
-                let addchild = AddChildWidget::new(&appdata.conn, Purpose::Source(id));
+                let addchild = AddChildWidget::new(&appdata.conn, Purpose::Source(id))?;
                 self.popup = Some(PopUp::AddChild(addchild));
             }
+            Action::EditTags(id) => {
+                self.popup = Some(PopUp::Tags(TagEditor::new(&appdata.conn, id)?));
+            }
             Action::PlayBackAudio(id) => {
-                Card::play_backaudio(&appdata.conn, id, &appdata.audio);
+                Card::play_backaudio(&appdata.conn, id, &appdata.audio)?;
             }
             Action::Refresh => {
+                let order = self.order;
                 *self = crate::tabs::review::logic::MainReview::new(
                     &appdata.conn,
                     &appdata.audio,
-                );
+                    &appdata.config,
+                )?;
+                if self.order != order {
+                    self.order = order;
+                    self.for_review.sort(&appdata.conn, &appdata.config, order)?;
+                    self.requeue_current();
+                    self.random_mode(&appdata.conn, &appdata.audio)?;
+                }
             }
             Action::None => {}
         }
+        Ok(())
     }
 }
 
+
 pub fn draw_done(f: &mut Frame<crate::MyType>, area: Rect) {
     let mut field = Field::new();
     field.replace_text("Nothing left to review now!\n\nYou could import anki cards from the import page, or add new cards manually.\n\nIf you've imported cards, press Alt+r here to refresh".to_string());
@@ -541,12 +972,14 @@ pub enum Action {
     Review(String, String, CardID, char),
     SkipUnf(String, String, CardID),
     SkipRev(String, String, CardID),
+    Suspend(String, String, CardID),
     CompleteUnf(String, String, CardID),
     NewDependency(CardID),
     NewDependent(CardID),
     AddDependency(CardID),
     AddDependent(CardID),
     AddChild(IncID),
+    EditTags(CardID),
     PlayBackAudio(CardID),
     Refresh,
     None,
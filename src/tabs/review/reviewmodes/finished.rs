@@ -1,11 +1,9 @@
-use std::sync::{Arc, Mutex};
-
-use rusqlite::Connection;
+use std::time::Instant;
 
 use crate::{
     tabs::review::logic::Action,
     utils::{
-        aliases::CardID, card::RecallGrade, sql::update::set_suspended, statelist::StatefulList,
+        aliases::CardID, card::RecallGrade, statelist::StatefulList,
     },
     widgets::{
         cardlist::CardItem, cardrater::CardRater, load_cards::MediaContents, textinput::Field,
@@ -32,10 +30,12 @@ pub struct CardReview {
     pub selection: ReviewSelection,
     pub cardrater: CardRater,
     pub media: MediaContents,
+    pub shown: Instant,
+    pub revealed: Option<Instant>,
 }
 
 impl CardReview {
-    pub fn keyhandler(&mut self, conn: &Arc<Mutex<Connection>>, key: MyKey, action: &mut Action) {
+    pub fn keyhandler(&mut self, key: MyKey, action: &mut Action) {
         use MyKey::*;
         use ReviewSelection::*;
 
@@ -55,9 +55,9 @@ impl CardReview {
             (_, Alt('y')) => *action = Action::NewDependency(self.id),
             (_, Alt('T')) => *action = Action::AddDependent(self.id),
             (_, Alt('Y')) => *action = Action::AddDependency(self.id),
+            (_, Alt('e')) => *action = Action::EditTags(self.id),
             (_, Alt('i')) => {
-                set_suspended(conn, self.id, true).unwrap();
-                *action = Action::SkipRev(
+                *action = Action::Suspend(
                     self.question.return_text(),
                     self.answer.return_text(),
                     self.id,
@@ -65,6 +65,7 @@ impl CardReview {
             }
             (RevealButton, Char(' ')) | (RevealButton, Enter) => {
                 self.reveal = true;
+                self.revealed = Some(Instant::now());
                 self.selection = CardRater;
                 *action = Action::PlayBackAudio(self.id);
             }
@@ -107,6 +108,17 @@ impl CardReview {
             (_, _) => {}
         }
     }
+    /// Seconds until the answer was revealed and seconds since then, -1 if unknown.
+    pub fn times(&self) -> (f32, f32) {
+        match self.revealed {
+            Some(revealed) => (
+                revealed.duration_since(self.shown).as_secs_f32(),
+                revealed.elapsed().as_secs_f32(),
+            ),
+            None => (-1., -1.),
+        }
+    }
+
     fn rev_nav(&mut self, dir: &crate::Direction) {
         use crate::Direction::*;
         use ReviewSelection::*;
@@ -143,7 +155,11 @@ impl CardReview {
         add old card as dependency: Alt+y
         add new card as dependency: Alt+Y
         suspend card: Alt+i
+        edit tags: Alt+e
+        move card to the trash: Ctrl+Delete
         rate card: 1,2,3,4
+        undo last review or suspend: Ctrl+z
+        switch review order: Alt+o
                 "#
         .to_string()
     }
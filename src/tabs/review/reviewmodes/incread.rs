@@ -2,6 +2,7 @@ use std::sync::{Arc, Mutex};
 
 use rusqlite::Connection;
 
+use crate::error::Result;
 use crate::{
     tabs::review::logic::Action,
     utils::{aliases::IncID, incread::IncRead},
@@ -26,13 +27,18 @@ pub enum IncSelection {
 
 
 impl IncMode {
-    pub fn keyhandler(&mut self, conn: &Arc<Mutex<Connection>>, key: MyKey, action: &mut Action) {
+    pub fn keyhandler(
+        &mut self,
+        conn: &Arc<Mutex<Connection>>,
+        key: MyKey,
+        action: &mut Action,
+    ) -> Result<()> {
         use IncSelection::*;
         use MyKey::*;
 
         if let MyKey::Nav(dir) = &key {
             self.inc_nav(dir);
-            return;
+            return Ok(());
         }
         match (&self.selection, key) {
             (_, Alt('d')) => {
@@ -50,9 +56,10 @@ impl IncMode {
                 )
             }
             (Source, Alt('a')) => *action = Action::AddChild(self.id),
-            (Source, key) => self.source.keyhandler(conn, key),
+            (Source, key) => self.source.keyhandler(conn, key)?,
             (_, _) => {}
         }
+        Ok(())
     }
     fn inc_nav(&mut self, dir: &crate::Direction) {
         use crate::Direction::*;
@@ -75,6 +82,7 @@ impl IncMode {
             
         Mark text as done: Alt+d
         skip text: Alt+s
+        move text to the trash: Ctrl+Delete
         make extract (visual mode): Alt+x 
         make cloze (visual mode): Alt+z
         add child card(in text widget): Alt+a
@@ -1,11 +1,12 @@
 use crate::app::AppData;
+use crate::error::Result;
 use crate::utils::misc::get_gpt3_response;
 
 use crate::utils::statelist::StatefulList;
 use crate::widgets::cardlist::CardItem;
 use crate::{
     tabs::review::logic::Action,
-    utils::{aliases::CardID, sql::update::set_suspended},
+    utils::aliases::CardID,
     widgets::textinput::Field,
     MyKey,
 };
@@ -27,13 +28,18 @@ pub enum UnfSelection {
 }
 
 impl UnfCard {
-    pub fn keyhandler(&mut self, appdata: &AppData, key: MyKey, action: &mut Action) {
+    pub fn keyhandler(
+        &mut self,
+        appdata: &AppData,
+        key: MyKey,
+        action: &mut Action,
+    ) -> Result<()> {
         use MyKey::*;
         use UnfSelection::*;
 
         if let MyKey::Nav(dir) = &key {
             self.unf_nav(dir);
-            return;
+            return Ok(());
         }
         match (&self.selection, key) {
             (_, Alt('s')) => {
@@ -54,15 +60,15 @@ impl UnfCard {
             (_, Alt('y')) => *action = Action::NewDependency(self.id),
             (_, Alt('T')) => *action = Action::AddDependent(self.id),
             (_, Alt('Y')) => *action = Action::AddDependency(self.id),
+            (_, Alt('e')) => *action = Action::EditTags(self.id),
             (_, Alt('g')) => {
                 if let Some(key) = &appdata.config.gptkey {
-                    let answer = get_gpt3_response(key, &self.question.return_text());
+                    let answer = get_gpt3_response(key, &self.question.return_text())?;
                     self.answer.replace_text(answer);
                 }
             }
             (_, Alt('i')) => {
-                set_suspended(&appdata.conn, self.id, true).unwrap();
-                *action = Action::SkipRev(
+                *action = Action::Suspend(
                     self.question.return_text(),
                     self.answer.return_text(),
                     self.id,
@@ -72,6 +78,7 @@ impl UnfCard {
             (Answer, key) => self.answer.keyhandler(key),
             (_, _) => {}
         }
+        Ok(())
     }
     fn unf_nav(&mut self, dir: &crate::Direction) {
         use crate::Direction::*;
@@ -103,6 +110,10 @@ impl UnfCard {
         add old card as dependency: Alt+y
         add new card as dependency: Alt+Y
         suspend card: Alt+i
+        edit tags: Alt+e
+        move card to the trash: Ctrl+Delete
+        undo last review or suspend: Ctrl+z
+        switch review order: Alt+o
 
                 "#
         .to_string()
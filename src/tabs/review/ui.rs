@@ -154,7 +154,7 @@ impl CardReview {
         let area = review_layout(area, false);
         let selected = RevSelect::new(&self.selection);
 
-        let resolved = is_resolved(conn, self.id);
+        let resolved = is_resolved(conn, self.id).unwrap_or(true);
         if !resolved && !self.reveal {
             self.selection = ReviewSelection::Answer;
         }
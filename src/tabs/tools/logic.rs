@@ -0,0 +1,402 @@
+use crate::app::AppData;
+use crate::app::Tab;
+use crate::error::{Result, SpekiError};
+use crate::utils::backup::{list_backups, restore as restore_backup, Snapshot};
+use crate::utils::forecast::{forecast, Forecast};
+use crate::utils::interval::recompute_all;
+use crate::utils::leech::leech_report;
+use crate::utils::misc::{centered_rect, split_leftright, split_updown};
+use crate::utils::optimizer::{optimize_revlog, save_result, OptimizeResult};
+use crate::utils::scheduler::new_scheduler;
+use crate::utils::sql::trash::{load_trash, purge_item, restore, TrashItem};
+use crate::utils::statelist::StatefulList;
+use crate::widgets::message_box::draw_message;
+use crate::widgets::textinput::Field;
+use crate::Direction;
+use crate::MyKey;
+use crate::MyType;
+
+use std::fmt;
+use std::sync::{mpsc, Arc};
+use std::thread;
+use tui::layout::Rect;
+use tui::style::{Color, Style};
+use tui::widgets::{BarChart, Block, Borders, Clear};
+use tui::Frame;
+
+#[derive(Clone, Copy)]
+pub enum Section {
+    Optimizer,
+    Forecast,
+    Leeches,
+    Recompute,
+    Trash,
+    Backups,
+    Profiles,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Section::Optimizer => "Optimize scheduler",
+            Section::Forecast => "Workload forecast",
+            Section::Leeches => "Leeches",
+            Section::Recompute => "Recompute all cards",
+            Section::Trash => "Trash",
+            Section::Backups => "Restore a backup",
+            Section::Profiles => "Switch profile",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(PartialEq)]
+pub enum Selection {
+    Sections,
+    Output,
+}
+
+pub struct Tools {
+    pub sections: StatefulList<Section>,
+    pub selection: Selection,
+    pub output: Field,
+    pub forecast: Option<Forecast>,
+    pub trash: Option<StatefulList<TrashItem>>,
+    pub backups: Option<StatefulList<Snapshot>>,
+    /// The backup that enter was pressed on, restored once it's pressed again.
+    pub confirm_restore: Option<usize>,
+    pub profiles: Option<StatefulList<String>>,
+    /// The optimizer running in the background, it sends its result when done.
+    pub optimizing: Option<mpsc::Receiver<rusqlite::Result<OptimizeResult>>>,
+}
+
+impl Default for Tools {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tools {
+    pub fn new() -> Self {
+        let mut sections = StatefulList::with_items(vec![
+            Section::Optimizer,
+            Section::Forecast,
+            Section::Leeches,
+            Section::Recompute,
+            Section::Trash,
+            Section::Backups,
+            Section::Profiles,
+        ]);
+        sections.next();
+        Tools {
+            sections,
+            selection: Selection::Sections,
+            output: Field::new(),
+            forecast: None,
+            trash: None,
+            backups: None,
+            confirm_restore: None,
+            profiles: None,
+            optimizing: None,
+        }
+    }
+
+    fn run(&mut self, appdata: &mut AppData) -> Result<()> {
+        let section = match self.sections.copy_selected() {
+            Some(section) => section,
+            None => return Ok(()),
+        };
+        self.forecast = None;
+        self.trash = None;
+        self.backups = None;
+        self.confirm_restore = None;
+        self.profiles = None;
+        let report = match section {
+            Section::Optimizer if self.optimizing.is_some() => {
+                "Still fitting the scheduler to your review history...".to_string()
+            }
+            Section::Optimizer => {
+                let (tx, rx) = mpsc::channel();
+                let conn = Arc::clone(&appdata.conn);
+                let config = &appdata.config;
+                let (kind, classic, fsrs) = (
+                    config.scheduler,
+                    config.classic.clone(),
+                    config.fsrs.clone(),
+                );
+                thread::spawn(move || {
+                    let scheduler = new_scheduler(kind, &classic, &fsrs);
+                    let _ = tx.send(optimize_revlog(&conn, &*scheduler));
+                });
+                self.optimizing = Some(rx);
+                "Fitting the scheduler to your review history...".to_string()
+            }
+            Section::Forecast => {
+                let days = appdata.config.forecast.days;
+                let new_per_day = appdata.config.forecast.new_per_day;
+                let forecast = forecast(&appdata.conn, &appdata.config, days, new_per_day)?;
+                let report = forecast.to_string();
+                self.forecast = Some(forecast);
+                report
+            }
+            Section::Leeches => leech_report(&appdata.conn)?,
+            Section::Recompute => recompute_all(&appdata.conn, &appdata.config)?.to_string(),
+            Section::Trash => {
+                let mut trash = StatefulList::with_items(load_trash(&appdata.conn)?);
+                trash.next();
+                self.trash = Some(trash);
+                self.selection = Selection::Output;
+                return Ok(());
+            }
+            Section::Backups => {
+                let mut backups = StatefulList::with_items(list_backups(&appdata.paths.backups)?);
+                backups.next();
+                self.backups = Some(backups);
+                self.selection = Selection::Output;
+                return Ok(());
+            }
+            Section::Profiles => {
+                let mut profiles = StatefulList::with_items(appdata.paths.profiles());
+                profiles.next();
+                self.profiles = Some(profiles);
+                self.selection = Selection::Output;
+                return Ok(());
+            }
+        };
+        self.output.replace_text(report);
+        Ok(())
+    }
+
+    fn trash_keyhandler(&mut self, appdata: &mut AppData, key: MyKey) -> Result<()> {
+        let trash = match &mut self.trash {
+            Some(trash) => trash,
+            None => return Ok(()),
+        };
+        let selected = trash.state.selected();
+        match key {
+            MyKey::Char('k') | MyKey::Up => trash.previous(),
+            MyKey::Char('j') | MyKey::Down => trash.next(),
+            MyKey::Enter | MyKey::Delete => {
+                let idx = match selected {
+                    Some(idx) if idx < trash.items.len() => idx,
+                    _ => return Ok(()),
+                };
+                let id = trash.items[idx].id;
+                if key == MyKey::Enter {
+                    restore(&appdata.conn, id)?;
+                } else {
+                    purge_item(&appdata.conn, id)?;
+                }
+                trash.items.remove(idx);
+                if idx == trash.items.len() {
+                    trash.previous();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn backups_keyhandler(&mut self, appdata: &mut AppData, key: MyKey) -> Result<()> {
+        let backups = match &mut self.backups {
+            Some(backups) => backups,
+            None => return Ok(()),
+        };
+        if let Some(idx) = self.confirm_restore.take() {
+            if key == MyKey::Enter {
+                let backup = &backups.items[idx];
+                let previous = restore_backup(&appdata.conn, &backup.path, &appdata.paths.backups)?;
+                appdata.reload = Some(format!(
+                    "Restored the backup from {}.\nThe database as it was before is in {}",
+                    backup.taken.format("%Y-%m-%d %H:%M"),
+                    previous.display()
+                ));
+            }
+            return Ok(());
+        }
+        match key {
+            MyKey::Char('k') | MyKey::Up => backups.previous(),
+            MyKey::Char('j') | MyKey::Down => backups.next(),
+            MyKey::Enter => match backups.state.selected() {
+                Some(idx) if idx < backups.items.len() => self.confirm_restore = Some(idx),
+                _ => {}
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn profiles_keyhandler(&mut self, appdata: &mut AppData, key: MyKey) -> Result<()> {
+        let profiles = match &mut self.profiles {
+            Some(profiles) => profiles,
+            None => return Ok(()),
+        };
+        match key {
+            MyKey::Char('k') | MyKey::Up => profiles.previous(),
+            MyKey::Char('j') | MyKey::Down => profiles.next(),
+            MyKey::Enter => {
+                let name = match profiles.state.selected() {
+                    Some(idx) if idx < profiles.items.len() => &profiles.items[idx],
+                    _ => return Ok(()),
+                };
+                if !appdata.paths.is_profile(name) {
+                    appdata.switch_to = Some(appdata.paths.switch_profile(name)?);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl Tab for Tools {
+    fn get_title(&self) -> String {
+        "Tools".to_string()
+    }
+
+    fn get_manual(&self) -> String {
+        r#"
+
+Maintenance tasks for your collection. Select one and press enter to run it,
+the result shows up on the right.
+
+Workload forecast: projects how many reviews are due each day, assuming the amount of new cards per day from the [forecast] section of the config file.
+
+Leeches: lists the cards that you keep forgetting.
+
+Optimize scheduler: fits the parameters of the active scheduler to your review history and saves them to the config file. It runs in the background, the result shows up here when it's done.
+
+Recompute all cards: replays every card's review history through the active scheduler. Run it after switching schedulers or changing their parameters.
+
+Trash: deleted cards, topics and texts. Press enter on one to restore it, or delete to remove it for good. They're removed by themselves after trash_retention_days from the config file.
+
+Restore a backup: snapshots of the database, taken when you quit, before imports and before upgrades. Press enter on one, and enter again to confirm, to replace your collection with it. A snapshot of the collection as it was is taken first, so this can be undone the same way.
+
+Switch profile: opens the collection of another profile. New profiles are made by starting speki with --profile and a name.
+
+        "#
+        .to_string()
+    }
+
+    fn tick(&mut self, appdata: &mut AppData) -> Result<()> {
+        let result = match &self.optimizing {
+            Some(rx) => match rx.try_recv() {
+                Ok(result) => result,
+                Err(mpsc::TryRecvError::Empty) => return Ok(()),
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.optimizing = None;
+                    return Err(SpekiError::Other(
+                        "the optimizer stopped unexpectedly".to_string(),
+                    ));
+                }
+            },
+            None => return Ok(()),
+        };
+        self.optimizing = None;
+        let result = result?;
+        let path = appdata.paths.config.clone();
+        self.output.replace_text(save_result(&result, &mut appdata.config, &path));
+        Ok(())
+    }
+
+    fn keyhandler(&mut self, appdata: &mut AppData, key: MyKey) -> Result<()> {
+        use MyKey::*;
+        use Selection::*;
+        match (&self.selection, key) {
+            (_, Nav(Direction::Right)) => self.selection = Output,
+            (_, Nav(Direction::Left)) => self.selection = Sections,
+            (Sections, Enter) => self.run(appdata)?,
+            (Sections, Char('k')) | (Sections, Up) => self.sections.previous(),
+            (Sections, Char('j')) | (Sections, Down) => self.sections.next(),
+            (Output, key) if self.trash.is_some() => self.trash_keyhandler(appdata, key)?,
+            (Output, key) if self.backups.is_some() => self.backups_keyhandler(appdata, key)?,
+            (Output, key) if self.profiles.is_some() => self.profiles_keyhandler(appdata, key)?,
+            (Output, key) => self.output.keyhandler(key),
+            (_, _) => {}
+        }
+        Ok(())
+    }
+
+    fn render(&mut self, f: &mut Frame<MyType>, _appdata: &AppData, area: Rect) {
+        let chunks = split_leftright([30, 70], area);
+        self.sections.render(
+            f,
+            chunks[0],
+            self.selection == Selection::Sections,
+            "Tools",
+            Style::default(),
+        );
+        if let Some(trash) = &mut self.trash {
+            trash.render(
+                f,
+                chunks[1],
+                self.selection == Selection::Output,
+                "Trash (enter: restore, delete: remove for good)",
+                Style::default(),
+            );
+            return;
+        }
+        if let Some(backups) = &mut self.backups {
+            backups.render(
+                f,
+                chunks[1],
+                self.selection == Selection::Output,
+                "Backups (enter: restore)",
+                Style::default(),
+            );
+            if let Some(idx) = self.confirm_restore {
+                let area = centered_rect(60, 40, chunks[1]);
+                f.render_widget(Clear, area);
+                draw_message(
+                    f,
+                    area,
+                    &format!(
+                        "Replace the collection with the backup from {}? A snapshot of it as it is now is taken first.\n\nEnter: restore, any other key: cancel",
+                        backups.items[idx].taken.format("%Y-%m-%d %H:%M")
+                    ),
+                );
+            }
+            return;
+        }
+        if let Some(profiles) = &mut self.profiles {
+            profiles.render(
+                f,
+                chunks[1],
+                self.selection == Selection::Output,
+                "Profiles (enter: switch)",
+                Style::default(),
+            );
+            return;
+        }
+        let output = match &self.forecast {
+            Some(forecast) => {
+                let right = split_updown([40, 60], chunks[1]);
+                draw_forecast(f, right[0], forecast);
+                right[1]
+            }
+            None => chunks[1],
+        };
+        self.output
+            .render(f, output, self.selection == Selection::Output);
+    }
+}
+
+fn draw_forecast(f: &mut Frame<MyType>, area: Rect, forecast: &Forecast) {
+    let labels: Vec<String> = (0..forecast.days()).map(|day| day.to_string()).collect();
+    let data: Vec<(&str, u64)> = labels
+        .iter()
+        .zip(&forecast.due)
+        .map(|(label, due)| (label.as_str(), *due as u64))
+        .collect();
+    let chart = BarChart::default()
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Reviews due per day"),
+        )
+        .data(&data)
+        .bar_width(3)
+        .bar_style(Style::default().fg(Color::Cyan))
+        .value_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+    f.render_widget(chart, area);
+}
@@ -1,6 +1,7 @@
 pub type TopicID = u32;
 pub type CardID = u32;
 pub type IncID = u32;
+pub type RevlogID = i64;
 
 pub type ModelID = u64;
 pub type NoteID = u64;
@@ -0,0 +1,363 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Datelike, Local, NaiveDateTime};
+use rusqlite::backup::Progress;
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+
+use crate::app::BackupConfig;
+use crate::error::{Result, SpekiError};
+use crate::utils::sql::migrations::{latest_version, migrate, user_version};
+
+const TIMEFORMAT: &str = "%Y-%m-%d_%H%M%S";
+/// How long the timestamps in the file names are.
+const TIMELEN: usize = "2000-01-01_000000".len();
+
+/// Which day, week or month a snapshot was taken in.
+type Bucket = fn(&NaiveDateTime) -> (i32, u32);
+
+/// A copy of the database in the backup folder.
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub taken: NaiveDateTime,
+    /// What it was taken for, like "quit" or "import". Backups from before snapshots had
+    /// names of their own just have their file name here.
+    pub reason: String,
+    /// Whether it follows the naming of `snapshot`, only those get pruned.
+    managed: bool,
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}  {}", self.taken.format("%Y-%m-%d %H:%M"), self.reason)
+    }
+}
+
+impl Snapshot {
+    fn from_path(path: PathBuf) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_string();
+        let parsed = name
+            .strip_prefix("backup_")
+            .and_then(|rest| rest.strip_suffix(".db"))
+            .filter(|rest| rest.len() > TIMELEN + 1)
+            .and_then(|rest| {
+                let (time, reason) = rest.split_at(TIMELEN);
+                let time = NaiveDateTime::parse_from_str(time, TIMEFORMAT).ok()?;
+                Some((time, reason.strip_prefix('_')?.to_string()))
+            });
+        if let Some((taken, reason)) = parsed {
+            return Some(Snapshot {
+                path,
+                taken,
+                reason,
+                managed: true,
+            });
+        }
+        if !name.ends_with(".db") {
+            return None;
+        }
+        let modified: DateTime<Local> = path.metadata().ok()?.modified().ok()?.into();
+        Some(Snapshot {
+            path,
+            taken: modified.naive_local(),
+            reason: name,
+            managed: false,
+        })
+    }
+}
+
+/// Copies the open database into `dir` with sqlite's backup API, so it's consistent even
+/// while in use. The copy is only kept if it passes an integrity check.
+pub fn snapshot(conn: &Connection, dir: &Path, reason: &str) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let name = format!("backup_{}_{}.db", Local::now().format(TIMEFORMAT), reason);
+    let tmp = dir.join(format!("{}.tmp", name));
+    conn.backup(DatabaseName::Main, &tmp, None)?;
+    if let Err(err) = verify(&tmp, user_version(conn)?) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(err);
+    }
+    let path = dir.join(name);
+    std::fs::rename(&tmp, &path)?;
+    Ok(path)
+}
+
+/// Fails unless the file is an intact database with the given schema version.
+fn verify(path: &Path, version: u32) -> Result<()> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if integrity != "ok" {
+        return Err(SpekiError::Other(format!(
+            "the backup at {} is damaged: {}",
+            path.display(),
+            integrity
+        )));
+    }
+    let found = user_version(&conn)?;
+    if found != version {
+        return Err(SpekiError::Other(format!(
+            "the backup at {} has schema version {} instead of {}",
+            path.display(),
+            found,
+            version
+        )));
+    }
+    Ok(())
+}
+
+/// Every backup in `dir`, newest first.
+pub fn list_backups(dir: &Path) -> Result<Vec<Snapshot>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut snapshots = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        if let Some(snapshot) = Snapshot::from_path(entry?.path()) {
+            snapshots.push(snapshot);
+        }
+    }
+    snapshots.sort_by_key(|snapshot| Reverse(snapshot.taken));
+    Ok(snapshots)
+}
+
+/// Thins out the snapshots to the newest one of each of the last `daily` days, `weekly`
+/// weeks and `monthly` months. The newest snapshot of every reason is kept regardless, so
+/// there's always one from before the last import or migration. Returns how many were removed.
+pub fn prune(dir: &Path, config: &BackupConfig) -> Result<usize> {
+    let snapshots: Vec<Snapshot> = list_backups(dir)?
+        .into_iter()
+        .filter(|snapshot| snapshot.managed)
+        .collect();
+
+    let mut keep: HashSet<&Path> = HashSet::new();
+    let mut reasons = HashSet::new();
+    for snapshot in &snapshots {
+        if reasons.insert(snapshot.reason.as_str()) {
+            keep.insert(&snapshot.path);
+        }
+    }
+    let buckets: [(usize, Bucket); 3] = [
+        (config.daily, |time| (time.year(), time.ordinal())),
+        (config.weekly, |time| {
+            let week = time.iso_week();
+            (week.year(), week.week())
+        }),
+        (config.monthly, |time| (time.year(), time.month())),
+    ];
+    for (count, bucket) in buckets {
+        let mut seen = HashSet::new();
+        for snapshot in &snapshots {
+            if seen.len() == count {
+                break;
+            }
+            if seen.insert(bucket(&snapshot.taken)) {
+                keep.insert(&snapshot.path);
+            }
+        }
+    }
+
+    let mut removed = 0;
+    for snapshot in &snapshots {
+        if !keep.contains(snapshot.path.as_path()) {
+            std::fs::remove_file(&snapshot.path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Replaces the database with a backup. The database as it was gets snapshotted first, and
+/// backups from older versions of speki get migrated. If that fails the snapshot is put back.
+/// Returns where that snapshot went.
+pub fn restore(conn: &Arc<Mutex<Connection>>, backup: &Path, dir: &Path) -> Result<PathBuf> {
+    let version = {
+        let src = Connection::open_with_flags(backup, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        user_version(&src)?
+    };
+    if version > latest_version() {
+        return Err(SpekiError::Other(format!(
+            "the backup at {} is from a newer version of speki",
+            backup.display()
+        )));
+    }
+    verify(backup, version)?;
+
+    let mut conn = conn.lock().unwrap();
+    let previous = snapshot(&conn, dir, "prerestore")?;
+    conn.restore(DatabaseName::Main, backup, None::<fn(Progress)>)?;
+    if let Err(err) = migrate(&mut conn, backup, dir, false) {
+        conn.restore(DatabaseName::Main, &previous, None::<fn(Progress)>)?;
+        // a migration that failed halfway leaves them off
+        conn.pragma_update(None, "foreign_keys", true)?;
+        return Err(err.into());
+    }
+    Ok(previous)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty folder of its own for every test, prune only looks at the file names.
+    fn backup_dir(test: &str, names: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "speki-prune-{}-{}",
+            test,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in names {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        dir
+    }
+
+    fn prune_with(
+        test: &str,
+        daily: usize,
+        weekly: usize,
+        monthly: usize,
+        names: &[&str],
+    ) -> (usize, Vec<String>) {
+        let dir = backup_dir(test, names);
+        let config = BackupConfig {
+            daily,
+            weekly,
+            monthly,
+        };
+        let removed = prune(&dir, &config).unwrap();
+        let mut left: Vec<String> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        std::fs::remove_dir_all(&dir).unwrap();
+        (removed, left)
+    }
+
+    #[test]
+    fn keeps_the_newest_of_each_day() {
+        let (removed, left) = prune_with(
+            "daily",
+            3,
+            0,
+            0,
+            &[
+                "backup_2026-10-17_120000_quit.db",
+                "backup_2026-10-17_080000_quit.db",
+                "backup_2026-10-16_120000_quit.db",
+                "backup_2026-10-15_120000_quit.db",
+                "backup_2026-10-14_120000_quit.db",
+            ],
+        );
+        assert_eq!(removed, 2);
+        assert_eq!(
+            left,
+            [
+                "backup_2026-10-15_120000_quit.db",
+                "backup_2026-10-16_120000_quit.db",
+                "backup_2026-10-17_120000_quit.db",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_newest_of_each_iso_week() {
+        // the 11th is a sunday, so it's in the week before the 12th
+        let (removed, left) = prune_with(
+            "weekly",
+            0,
+            2,
+            0,
+            &[
+                "backup_2026-10-16_120000_quit.db",
+                "backup_2026-10-12_120000_quit.db",
+                "backup_2026-10-11_120000_quit.db",
+                "backup_2026-10-05_120000_quit.db",
+                "backup_2026-10-04_120000_quit.db",
+            ],
+        );
+        assert_eq!(removed, 3);
+        assert_eq!(
+            left,
+            [
+                "backup_2026-10-11_120000_quit.db",
+                "backup_2026-10-16_120000_quit.db",
+            ]
+        );
+    }
+
+    #[test]
+    fn weeks_span_the_new_year() {
+        let (removed, left) = prune_with(
+            "newyear",
+            0,
+            1,
+            0,
+            &[
+                "backup_2027-01-01_120000_quit.db",
+                "backup_2026-12-28_120000_quit.db",
+            ],
+        );
+        assert_eq!(removed, 1);
+        assert_eq!(left, ["backup_2027-01-01_120000_quit.db"]);
+    }
+
+    #[test]
+    fn keeps_the_newest_of_each_month() {
+        let (removed, left) = prune_with(
+            "monthly",
+            0,
+            0,
+            2,
+            &[
+                "backup_2026-10-02_120000_quit.db",
+                "backup_2026-09-30_120000_quit.db",
+                "backup_2026-09-01_120000_quit.db",
+                "backup_2026-08-15_120000_quit.db",
+            ],
+        );
+        assert_eq!(removed, 2);
+        assert_eq!(
+            left,
+            [
+                "backup_2026-09-30_120000_quit.db",
+                "backup_2026-10-02_120000_quit.db",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_newest_of_each_reason_and_foreign_files() {
+        let (removed, left) = prune_with(
+            "reasons",
+            1,
+            0,
+            0,
+            &[
+                "backup_2026-10-17_120000_quit.db",
+                "backup_2026-10-10_120000_quit.db",
+                "backup_2026-09-01_120000_import.db",
+                "backup_2026-06-01_120000_import.db",
+                "backup_2026-01-01_120000_premigration-v6.db",
+                "backup_17_10_2026_dbflash.db",
+                "notes.txt",
+            ],
+        );
+        assert_eq!(removed, 2);
+        assert_eq!(
+            left,
+            [
+                "backup_17_10_2026_dbflash.db",
+                "backup_2026-01-01_120000_premigration-v6.db",
+                "backup_2026-09-01_120000_import.db",
+                "backup_2026-10-17_120000_quit.db",
+                "notes.txt",
+            ]
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use crate::utils::sql::fetch::fetch_card;
 use rusqlite::Connection;
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::utils::misc::unix_now;
 
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
@@ -79,22 +79,44 @@ impl Status {
 pub struct Review {
     pub grade: RecallGrade,
     pub date: u32,
+    pub questiontime: f32,
     pub answertime: f32,
 }
 
 impl Review {
     pub fn from(grade: &RecallGrade) -> Review {
-        let unix = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as u32;
+        let unix = unix_now();
 
         Review {
             grade: grade.clone(),
             date: unix,
+            questiontime: -1.,
             answertime: -1 as f32,
         }
     }
+
+    /// Seconds the question was shown before the answer was revealed, and seconds
+    /// spent grading after that.
+    pub fn with_times(mut self, questiontime: f32, answertime: f32) -> Review {
+        self.questiontime = questiontime;
+        self.answertime = answertime;
+        self
+    }
+}
+
+/// Everything about a card that reviewing, suspending or changing its type can touch,
+/// so it can be put back the way it was.
+#[derive(Debug, Clone)]
+pub struct CardState {
+    pub cardtype: CardType,
+    pub suspended: bool,
+    /// strength and stability
+    pub finished: Option<(f32, f32)>,
+    /// skiptime and skipduration
+    pub unfinished: Option<(u32, u32)>,
+    pub pending: Option<u32>,
+    /// lapses and when it was flagged
+    pub leech: Option<(u32, u32)>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -120,6 +142,12 @@ pub struct Card {
     pub dependents: Vec<IncID>,
     pub topic: TopicID,
     pub source: IncID,
+    /// Unix time, set when the card is saved.
+    pub created: u32,
+    /// Unix time of the last change to the question, answer, topic or source.
+    pub modified: u32,
+    /// Only filled in by `fetch_card`.
+    pub tags: Vec<String>,
 }
 
 impl Card {
@@ -143,6 +171,9 @@ impl Card {
             dependents: vec![],
             topic: 0,
             source: 0,
+            created: 0,
+            modified: 0,
+            tags: vec![],
         }
     }
 
@@ -166,6 +197,10 @@ impl Card {
         self.topic = topic;
         self
     }
+    pub fn tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
     pub fn frontaudio(mut self, audiopath: Option<PathBuf>) -> Self {
         self.frontaudio = audiopath;
         self
@@ -211,35 +246,35 @@ impl Card {
         false
     }
 
-    pub fn save_card(self, conn: &Arc<Mutex<Connection>>) -> CardID {
+    pub fn save_card(self, conn: &Arc<Mutex<Connection>>) -> Result<CardID> {
         let dependencies = self.dependencies.clone();
         let dependents = self.dependents.clone();
         let finished = self.is_complete();
-        let card_id = save_card(&conn, self);
+        let card_id = save_card(conn, self)?;
 
         if finished {
-            revlog_new(&conn, card_id, Review::from(&RecallGrade::Decent)).unwrap();
+            revlog_new(conn, card_id, Review::from(&RecallGrade::Decent))?;
         }
 
         for dependency in dependencies {
-            update_both(&conn, card_id, dependency).unwrap();
+            update_both(conn, card_id, dependency)?;
         }
         for dependent in dependents {
-            update_both(&conn, dependent, card_id).unwrap();
-            Self::check_resolved(dependent, conn);
+            update_both(conn, dependent, card_id)?;
+            Self::check_resolved(dependent, conn)?;
         }
 
-        Self::check_resolved(card_id, conn);
-        card_id
+        Self::check_resolved(card_id, conn)?;
+        Ok(card_id)
     }
 
-    pub fn check_resolved(id: u32, conn: &Arc<Mutex<Connection>>) -> bool {
+    pub fn check_resolved(id: u32, conn: &Arc<Mutex<Connection>>) -> Result<bool> {
         let mut change_detected = false;
-        let mut card = fetch_card(&conn, id);
+        let mut card = fetch_card(conn, id)?;
         let mut is_resolved = true;
 
         for dependency in &card.dependencies {
-            let dep_card = fetch_card(&conn, *dependency);
+            let dep_card = fetch_card(conn, *dependency)?;
             if !dep_card.resolved || !dep_card.is_complete() {
                 is_resolved = false;
                 break;
@@ -248,54 +283,86 @@ impl Card {
         if card.resolved != is_resolved {
             change_detected = true;
             card.resolved = is_resolved;
-            set_resolved(&conn, card.id, card.resolved).unwrap();
+            set_resolved(&conn.lock().unwrap(), card.id, card.resolved)?;
 
             for dependent in card.dependents {
-                Card::check_resolved(dependent, conn);
+                Card::check_resolved(dependent, conn)?;
             }
         }
-        change_detected
+        Ok(change_detected)
+    }
+
+    pub fn new_review(
+        conn: &Arc<Mutex<Connection>>,
+        id: CardID,
+        review: Review,
+        scheduler: &dyn Scheduler,
+    ) -> Result<RevlogID> {
+        let rowid = revlog_new(conn, id, review)?;
+        super::interval::calc_stability(conn, id, scheduler)?;
+        Ok(rowid)
+    }
+    pub fn complete_card(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<RevlogID> {
+        let card = fetch_card(conn, id)?;
+        remove_unfinished(&conn.lock().unwrap(), id)?;
+        new_finished(conn, id)?;
+        let rowid = revlog_new(conn, id, Review::from(&RecallGrade::Decent))?;
+        for dependent in card.dependents {
+            Card::check_resolved(dependent, conn)?;
+        }
+        Ok(rowid)
     }
 
-    pub fn new_review(conn: &Arc<Mutex<Connection>>, id: CardID, review: RecallGrade) {
-        revlog_new(&conn, id, Review::from(&review)).unwrap();
-        super::interval::calc_stability(conn, id);
-    }
-    pub fn complete_card(conn: &Arc<Mutex<Connection>>, id: CardID) {
-        let card = fetch_card(&conn, id);
-        remove_unfinished(&conn, id).unwrap();
-        new_finished(&conn, id).unwrap();
-        revlog_new(&conn, id, Review::from(&RecallGrade::Decent)).unwrap();
+    /// Turns a finished card back into an unfinished one, so it comes up for rewriting.
+    pub fn uncomplete_card(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<()> {
+        let card = fetch_card(conn, id)?;
+        remove_finished(&conn.lock().unwrap(), id)?;
+        new_unfinished(conn, id)?;
+        set_cardtype(conn, id, CardType::Unfinished)?;
         for dependent in card.dependents {
-            Card::check_resolved(dependent, conn);
+            Card::check_resolved(dependent, conn)?;
         }
+        Ok(())
     }
 
-    pub fn activate_card(conn: &Arc<Mutex<Connection>>, id: CardID) {
-        remove_pending(&conn, id).unwrap();
-        new_finished(&conn, id).unwrap();
+    pub fn activate_card(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<()> {
+        remove_pending(&conn.lock().unwrap(), id)?;
+        new_finished(conn, id)?;
+        Ok(())
     }
 
-    pub fn play_frontaudio(conn: &Arc<Mutex<Connection>>, id: CardID, audio: &Option<Audio>) {
-        let card = fetch_card(&conn, id);
+    pub fn play_frontaudio(
+        conn: &Arc<Mutex<Connection>>,
+        id: CardID,
+        audio: &Option<Audio>,
+    ) -> Result<()> {
+        let card = fetch_card(conn, id)?;
         if let Some(path) = card.frontaudio {
             crate::utils::misc::play_audio(audio, path);
         }
+        Ok(())
     }
-    pub fn play_backaudio(conn: &Arc<Mutex<Connection>>, id: CardID, audio: &Option<Audio>) {
-        let card = fetch_card(&conn, id);
+    pub fn play_backaudio(
+        conn: &Arc<Mutex<Connection>>,
+        id: CardID,
+        audio: &Option<Audio>,
+    ) -> Result<()> {
+        let card = fetch_card(conn, id)?;
         if let Some(path) = card.backaudio {
             crate::utils::misc::play_audio(audio, path);
         }
+        Ok(())
     }
 }
 
-use super::sql::delete::{remove_pending, remove_unfinished};
-use super::sql::insert::new_finished;
+use super::scheduler::Scheduler;
+use super::sql::delete::{remove_finished, remove_pending, remove_unfinished};
+use super::sql::insert::{new_finished, new_unfinished};
 use super::sql::insert::revlog_new;
 use super::sql::{
     insert::{save_card, update_both},
-    update::set_resolved,
+    update::{set_cardtype, set_resolved},
 };
 use crate::app::Audio;
+use crate::error::Result;
 use crate::utils::aliases::*;
@@ -0,0 +1,435 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use rusqlite::Connection;
+
+use crate::error::Result;
+use crate::utils::aliases::*;
+use crate::utils::card::{CardType, RecallGrade, Review};
+use crate::utils::sql::delete::{
+    remove_dependency, remove_finished, remove_pending, remove_row, remove_unfinished,
+};
+use crate::utils::sql::fetch::{
+    cards_reviewed_since, foreign_key_violations, get_incread_ids, get_state_rows, get_topics,
+    load_cards,
+};
+use crate::utils::sql::insert::{insert_finished, insert_pending, insert_review, insert_unfinished};
+use crate::utils::sql::update::{set_resolved, update_card_source, update_card_topic};
+
+/// Something in the database that doesn't add up.
+pub enum Problem {
+    /// A row that refers to a card that doesn't exist anymore.
+    Orphan { table: String, rowid: i64 },
+    /// The card isn't in exactly one of the finished, unfinished and pending tables, the one
+    /// matching its type. `rows` are the tables it is in.
+    WrongState {
+        id: CardID,
+        cardtype: CardType,
+        rows: Vec<CardType>,
+    },
+    MissingTopic { topic: TopicID },
+    MissingSource { id: CardID, source: IncID },
+    /// Every card depends on the next, and the last one on the first.
+    Cycle(Vec<CardID>),
+    /// `resolved` is what it should be.
+    WrongResolved { id: CardID, resolved: bool },
+    /// A finished card that was never reviewed, so its strength can't be computed.
+    NoHistory(CardID),
+    /// Only reported, the file might be on a drive that isn't mounted or be put back later.
+    MissingMedia {
+        id: CardID,
+        column: &'static str,
+        path: PathBuf,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Orphan { table, rowid } => {
+                write!(f, "row {} of {} refers to a missing card", rowid, table)
+            }
+            Problem::WrongState { id, cardtype, rows } => write!(
+                f,
+                "card {} is {:?} but has {} state rows ({:?})",
+                id,
+                cardtype,
+                rows.len(),
+                rows
+            ),
+            Problem::MissingTopic { topic } => {
+                write!(f, "cards belong to topic {}, which doesn't exist", topic)
+            }
+            Problem::MissingSource { id, source } => write!(
+                f,
+                "card {} was made from text {}, which doesn't exist",
+                id, source
+            ),
+            Problem::Cycle(cards) => {
+                let cards: Vec<String> = cards.iter().map(|id| id.to_string()).collect();
+                write!(f, "dependency cycle between cards {}", cards.join(" -> "))
+            }
+            Problem::WrongResolved { id, resolved } => write!(
+                f,
+                "card {} should be {}",
+                id,
+                if *resolved { "resolved" } else { "unresolved" }
+            ),
+            Problem::NoHistory(id) => write!(f, "card {} is finished but was never reviewed", id),
+            Problem::MissingMedia { id, column, path } => {
+                write!(f, "{} of card {} is missing: {}", column, id, path.display())
+            }
+        }
+    }
+}
+
+impl Problem {
+    fn repair(&self, conn: &Connection) -> Result<()> {
+        match self {
+            Problem::Orphan { table, rowid } => remove_row(conn, table, *rowid)?,
+            Problem::WrongState { id, cardtype, rows } => {
+                // duplicates are all dropped for a fresh row, like the one it's missing
+                let matching = rows.iter().filter(|row| *row == cardtype).count();
+                if *cardtype != CardType::Finished || matching > 1 {
+                    remove_finished(conn, *id)?;
+                }
+                if *cardtype != CardType::Unfinished || matching > 1 {
+                    remove_unfinished(conn, *id)?;
+                }
+                if *cardtype != CardType::Pending || matching > 1 {
+                    remove_pending(conn, *id)?;
+                }
+                if matching != 1 {
+                    match cardtype {
+                        CardType::Finished => insert_finished(conn, *id)?,
+                        CardType::Unfinished => insert_unfinished(conn, *id)?,
+                        CardType::Pending => insert_pending(conn, *id)?,
+                    }
+                }
+            }
+            Problem::MissingTopic { topic } => update_card_topic(conn, *topic, 1)?,
+            Problem::MissingSource { id, .. } => update_card_source(conn, *id, 0)?,
+            Problem::Cycle(cards) => {
+                let last = *cards.last().unwrap();
+                remove_dependency(conn, last, cards[0])?;
+            }
+            Problem::WrongResolved { id, resolved } => set_resolved(conn, *id, *resolved)?,
+            Problem::NoHistory(id) => {
+                insert_review(conn, *id, Review::from(&RecallGrade::Decent))?;
+            }
+            Problem::MissingMedia { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+/// The outcome of `check`, every problem that was found.
+pub struct CheckReport {
+    pub problems: Vec<Problem>,
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.problems.is_empty() {
+            return writeln!(f, "no problems found");
+        }
+        writeln!(f, "found {} problems", self.problems.len())?;
+        for problem in &self.problems {
+            writeln!(f, "  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl CheckReport {
+    pub fn is_empty(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Like the full report, but only the first `max` problems are listed.
+    pub fn summary(&self, max: usize) -> String {
+        let mut summary = format!("found {} problems\n", self.problems.len());
+        for problem in self.problems.iter().take(max) {
+            summary.push_str(&format!("  {}\n", problem));
+        }
+        if self.problems.len() > max {
+            summary.push_str(&format!("  and {} more\n", self.problems.len() - max));
+        }
+        summary
+    }
+
+    /// Fixes every problem in the report. Orphaned rows and cycles get deleted, missing topics
+    /// and sources get unset, cards get the state rows their type calls for, and finished
+    /// cards without history get a review from today. Missing media is left as it is.
+    /// It all happens in one transaction, so a failed repair leaves the database as it was.
+    pub fn repair(&self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
+        let mut guard = conn.lock().unwrap();
+        let tx = guard.transaction()?;
+        for problem in &self.problems {
+            problem.repair(&tx)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// Looks through the whole database for things that don't add up, without changing anything.
+pub fn check(conn: &Arc<Mutex<Connection>>) -> Result<CheckReport> {
+    let mut problems = vec![];
+    for (table, rowid) in foreign_key_violations(conn)? {
+        problems.push(Problem::Orphan { table, rowid });
+    }
+
+    let cards = load_cards(conn)?;
+    let mut state_rows = get_state_rows(conn)?;
+    for card in &cards {
+        let rows = state_rows.remove(&card.id).unwrap_or_default();
+        if rows != [card.cardtype.clone()] {
+            problems.push(Problem::WrongState {
+                id: card.id,
+                cardtype: card.cardtype.clone(),
+                rows,
+            });
+        }
+    }
+
+    let topics: HashSet<TopicID> = get_topics(conn)?.iter().map(|topic| topic.id).collect();
+    let mut missing_topics: Vec<TopicID> = cards
+        .iter()
+        .map(|card| card.topic)
+        .filter(|topic| !topics.contains(topic))
+        .collect();
+    missing_topics.sort_unstable();
+    missing_topics.dedup();
+    for topic in missing_topics {
+        problems.push(Problem::MissingTopic { topic });
+    }
+
+    let sources: HashSet<IncID> = get_incread_ids(conn)?.into_iter().collect();
+    for card in &cards {
+        if card.source != 0 && !sources.contains(&card.source) {
+            problems.push(Problem::MissingSource {
+                id: card.id,
+                source: card.source,
+            });
+        }
+    }
+
+    // dependencies on cards that are gone are orphans, already reported above
+    let ids: HashSet<CardID> = cards.iter().map(|card| card.id).collect();
+    let graph: HashMap<CardID, Vec<CardID>> = cards
+        .iter()
+        .map(|card| {
+            let dependencies = card
+                .dependencies
+                .iter()
+                .copied()
+                .filter(|dependency| ids.contains(dependency))
+                .collect();
+            (card.id, dependencies)
+        })
+        .collect();
+    let cycles = find_cycles(&graph);
+    // whether cards should be resolved is judged as if the cycles were already broken
+    let broken: HashSet<(CardID, CardID)> = cycles
+        .iter()
+        .map(|cycle| (*cycle.last().unwrap(), cycle[0]))
+        .collect();
+    problems.extend(cycles.into_iter().map(Problem::Cycle));
+
+    let finished: HashSet<CardID> = cards
+        .iter()
+        .filter(|card| card.is_complete())
+        .map(|card| card.id)
+        .collect();
+    let mut resolved = HashMap::new();
+    for card in &cards {
+        let should_be = is_resolved(card.id, &graph, &finished, &broken, &mut resolved);
+        if card.resolved != should_be {
+            problems.push(Problem::WrongResolved {
+                id: card.id,
+                resolved: should_be,
+            });
+        }
+    }
+
+    let reviewed: HashSet<CardID> = cards_reviewed_since(conn, 0)?.into_iter().collect();
+    for card in &cards {
+        if card.is_complete() && !reviewed.contains(&card.id) {
+            problems.push(Problem::NoHistory(card.id));
+        }
+    }
+
+    for card in &cards {
+        let media = [
+            ("frontaudio", &card.frontaudio),
+            ("backaudio", &card.backaudio),
+            ("frontimg", &card.frontimage),
+            ("backimg", &card.backimage),
+        ];
+        for (column, path) in media {
+            if let Some(path) = path {
+                if !path.exists() {
+                    problems.push(Problem::MissingMedia {
+                        id: card.id,
+                        column,
+                        path: path.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(CheckReport { problems })
+}
+
+/// Every cycle in the dependency graph, found by following the dependencies depth first.
+/// Dropping the dependency from the last card of each cycle to the first leaves none.
+fn find_cycles(graph: &HashMap<CardID, Vec<CardID>>) -> Vec<Vec<CardID>> {
+    fn visit(
+        id: CardID,
+        graph: &HashMap<CardID, Vec<CardID>>,
+        done: &mut HashSet<CardID>,
+        path: &mut Vec<CardID>,
+        cycles: &mut Vec<Vec<CardID>>,
+    ) {
+        path.push(id);
+        for dependency in graph.get(&id).into_iter().flatten() {
+            if let Some(pos) = path.iter().position(|card| card == dependency) {
+                cycles.push(path[pos..].to_vec());
+            } else if !done.contains(dependency) {
+                visit(*dependency, graph, done, path, cycles);
+            }
+        }
+        path.pop();
+        done.insert(id);
+    }
+
+    let mut ids: Vec<CardID> = graph.keys().copied().collect();
+    ids.sort_unstable();
+    let mut done = HashSet::new();
+    let mut cycles = vec![];
+    for id in ids {
+        if !done.contains(&id) {
+            visit(id, graph, &mut done, &mut vec![], &mut cycles);
+        }
+    }
+    cycles
+}
+
+/// Same rule as `Card::check_resolved`: all dependencies finished and resolved themselves.
+fn is_resolved(
+    id: CardID,
+    graph: &HashMap<CardID, Vec<CardID>>,
+    finished: &HashSet<CardID>,
+    broken: &HashSet<(CardID, CardID)>,
+    memo: &mut HashMap<CardID, bool>,
+) -> bool {
+    if let Some(resolved) = memo.get(&id) {
+        return *resolved;
+    }
+    let mut resolved = true;
+    for dependency in graph.get(&id).into_iter().flatten() {
+        if broken.contains(&(id, *dependency)) {
+            continue;
+        }
+        if !finished.contains(dependency) || !is_resolved(*dependency, graph, finished, broken, memo) {
+            resolved = false;
+            break;
+        }
+    }
+    memo.insert(id, resolved);
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph(edges: &[(CardID, CardID)]) -> HashMap<CardID, Vec<CardID>> {
+        let mut graph: HashMap<CardID, Vec<CardID>> = HashMap::new();
+        for (dependent, dependency) in edges {
+            graph.entry(*dependent).or_default().push(*dependency);
+            graph.entry(*dependency).or_default();
+        }
+        graph
+    }
+
+    /// Drops the dependency from the last card of every cycle to the first, like the repair.
+    fn break_cycles(graph: &mut HashMap<CardID, Vec<CardID>>, cycles: &[Vec<CardID>]) {
+        for cycle in cycles {
+            let (last, first) = (*cycle.last().unwrap(), cycle[0]);
+            graph.get_mut(&last).unwrap().retain(|dependency| *dependency != first);
+        }
+    }
+
+    fn assert_breaks_all(edges: &[(CardID, CardID)]) -> Vec<Vec<CardID>> {
+        let mut graph = graph(edges);
+        let cycles = find_cycles(&graph);
+        break_cycles(&mut graph, &cycles);
+        assert!(find_cycles(&graph).is_empty(), "still cyclic after dropping {:?}", cycles);
+        cycles
+    }
+
+    #[test]
+    fn no_cycles() {
+        assert!(assert_breaks_all(&[(1, 2), (2, 3), (1, 3)]).is_empty());
+    }
+
+    #[test]
+    fn simple_cycle() {
+        assert_eq!(assert_breaks_all(&[(1, 2), (2, 3), (3, 1)]), vec![vec![1, 2, 3]]);
+    }
+
+    #[test]
+    fn self_dependency() {
+        assert_eq!(assert_breaks_all(&[(1, 1), (2, 1)]), vec![vec![1]]);
+    }
+
+    #[test]
+    fn nested_cycles() {
+        let cycles = assert_breaks_all(&[(1, 2), (2, 1), (2, 3), (3, 1), (3, 4), (4, 3)]);
+        assert_eq!(cycles, vec![vec![1, 2], vec![1, 2, 3], vec![3, 4]]);
+    }
+
+    #[test]
+    fn every_card_depends_on_every_other() {
+        let mut edges = vec![];
+        for dependent in 1..=5 {
+            for dependency in 1..=5 {
+                edges.push((dependent, dependency));
+            }
+        }
+        assert_breaks_all(&edges);
+    }
+
+    #[test]
+    fn resolved_needs_finished_and_resolved_dependencies() {
+        let graph = graph(&[(1, 2), (3, 4), (5, 3)]);
+        let finished: HashSet<CardID> = [2, 3].into_iter().collect();
+        let mut memo = HashMap::new();
+        let mut resolved = |id| is_resolved(id, &graph, &finished, &HashSet::new(), &mut memo);
+        assert!(resolved(1));
+        assert!(resolved(2));
+        // 4 isn't finished, so 3 isn't resolved, and neither is 5 even though 3 is finished
+        assert!(!resolved(3));
+        assert!(!resolved(5));
+    }
+
+    #[test]
+    fn resolved_ignores_broken_cycles() {
+        let graph = graph(&[(1, 2), (2, 1), (3, 3)]);
+        let cycles = find_cycles(&graph);
+        let broken: HashSet<(CardID, CardID)> = cycles
+            .iter()
+            .map(|cycle| (*cycle.last().unwrap(), cycle[0]))
+            .collect();
+        let finished: HashSet<CardID> = [1, 2, 3].into_iter().collect();
+        let mut memo = HashMap::new();
+        for id in [1, 2, 3] {
+            assert!(is_resolved(id, &graph, &finished, &broken, &mut memo));
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crate::utils::misc::unix_now;
+
+use rusqlite::Connection;
+
+use crate::app::Config;
+use crate::utils::aliases::*;
+use crate::utils::card::{CardType, RecallGrade, Review};
+use crate::utils::scheduler::{due_after, Scheduler};
+use crate::utils::sql::fetch::{get_all_histories, get_stability, get_topics, load_cards};
+
+/// Projected amount of reviews for each of the coming days. Day 0 is today and
+/// includes every card that is already overdue.
+pub struct Forecast {
+    pub due: Vec<u32>,
+    pub new: Vec<u32>,
+    pub topics: Vec<(String, Vec<u32>)>,
+}
+
+impl Forecast {
+    pub fn days(&self) -> usize {
+        self.due.len()
+    }
+}
+
+impl std::fmt::Display for Forecast {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{:>4} {:>6} {:>6}", "day", "due", "new")?;
+        for day in 0..self.days() {
+            writeln!(f, "{:>4} {:>6} {:>6}", day, self.due[day], self.new[day])?;
+        }
+        writeln!(f)?;
+        writeln!(f, "per topic (total, busiest day):")?;
+        for (name, counts) in &self.topics {
+            let total: u32 = counts.iter().sum();
+            let (peakday, peak) = counts
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, count)| **count)
+                .unwrap();
+            writeln!(f, "{}: {} reviews, {} on day {}", name, total, peak, peakday)?;
+        }
+        Ok(())
+    }
+}
+
+struct SimCard {
+    topic: TopicID,
+    retention: f32,
+    history: Vec<Review>,
+    due: u32,
+}
+
+impl SimCard {
+    // assumes every simulated review is passed
+    fn review(&mut self, scheduler: &dyn Scheduler, date: u32) {
+        self.history.push(Review {
+            grade: RecallGrade::Decent,
+            date,
+            questiontime: -1.,
+            answertime: -1.,
+        });
+        self.due = match scheduler.schedule(&self.history, self.retention) {
+            Some(schedule) => schedule.due,
+            None => u32::MAX,
+        };
+    }
+}
+
+/// Simulates the coming `days` days, reviewing every card on the day it falls below
+/// its retention and introducing `new_per_day` cards from the pending queue each day.
+pub fn forecast(
+    conn: &Arc<Mutex<Connection>>,
+    config: &Config,
+    days: usize,
+    new_per_day: u32,
+) -> rusqlite::Result<Forecast> {
+    let scheduler = config.scheduler();
+    let topics = get_topics(conn)?;
+    let mut histories: HashMap<CardID, Vec<Review>> =
+        get_all_histories(conn)?.into_iter().collect();
+    let now = unix_now();
+
+    let mut active = vec![];
+    let mut pending = vec![];
+    for card in load_cards(conn)? {
+        if card.suspended || !card.resolved {
+            continue;
+        }
+        let retention = config.retention(&topics, card.topic);
+        match card.cardtype {
+            CardType::Finished => {
+                let history = histories.remove(&card.id).unwrap_or_default();
+                let last = match history.last() {
+                    Some(review) => review.date,
+                    None => continue,
+                };
+                let stability = get_stability(conn, card.id)?;
+                let due = due_after(last, scheduler.interval(stability, retention));
+                active.push(SimCard {
+                    topic: card.topic,
+                    retention,
+                    history,
+                    due,
+                });
+            }
+            CardType::Pending => pending.push(SimCard {
+                topic: card.topic,
+                retention,
+                history: vec![],
+                due: u32::MAX,
+            }),
+            CardType::Unfinished => {}
+        }
+    }
+
+    let mut due = vec![0; days];
+    let mut new = vec![0; days];
+    let mut per_topic: HashMap<TopicID, Vec<u32>> = HashMap::new();
+    pending.reverse();
+
+    for day in 0..days {
+        let start = now + day as u32 * 86400;
+        let end = start + 86400;
+
+        for card in active.iter_mut().filter(|card| card.due < end) {
+            card.review(&*scheduler, std::cmp::max(card.due, start));
+            due[day] += 1;
+            per_topic.entry(card.topic).or_insert_with(|| vec![0; days])[day] += 1;
+        }
+
+        for _ in 0..new_per_day {
+            if let Some(mut card) = pending.pop() {
+                card.review(&*scheduler, start);
+                new[day] += 1;
+                active.push(card);
+            }
+        }
+    }
+
+    let mut topics: Vec<(String, Vec<u32>)> = per_topic
+        .into_iter()
+        .map(|(id, counts)| {
+            let name = topics
+                .iter()
+                .find(|topic| topic.id == id)
+                .map(|topic| topic.name.clone())
+                .unwrap_or_else(|| "no topic".to_string());
+            (name, counts)
+        })
+        .collect();
+    topics.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(Forecast { due, new, topics })
+}
@@ -1,4 +1,5 @@
 use super::aliases::*;
+use crate::error::Result;
 use super::card::Card;
 use super::sql::fetch::{get_incread, load_extracts, CardQuery};
 use super::sql::insert::new_incread;
@@ -34,17 +35,18 @@ pub struct IncRead {
 }
 
 impl IncRead {
-    pub fn new(conn: &Arc<Mutex<Connection>>, id: IncID) -> Self {
-        get_incread(&conn, id).unwrap()
+    pub fn new(conn: &Arc<Mutex<Connection>>, id: IncID) -> Result<Self> {
+        Ok(get_incread(conn, id)?)
     }
 
-    pub fn extract(&mut self, conn: &Arc<Mutex<Connection>>) {
+    pub fn extract(&mut self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
         if let Some(extract) = self.source.return_selection() {
-            new_incread(&conn, self.id, self.topic, extract, true).unwrap();
-            self.extracts = StatefulList::with_items(load_extracts(&conn, self.id).unwrap());
+            new_incread(conn, self.id, self.topic, extract, true)?;
+            self.extracts = StatefulList::with_items(load_extracts(conn, self.id)?);
         }
+        Ok(())
     }
-    pub fn cloze(&mut self, conn: &Arc<Mutex<Connection>>) {
+    pub fn cloze(&mut self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
         if let Some(cloze) = self.source.return_selection() {
             let mut question = self.source.return_text();
             question = question.replace(&cloze, "[...]");
@@ -56,30 +58,33 @@ impl IncRead {
                 .topic(self.topic)
                 .source(self.id)
                 .cardtype(super::card::CardType::Finished)
-                .save_card(conn);
+                .save_card(conn)?;
 
-            let cloze_cards = CardQuery::default().source(self.id).fetch_carditems(conn);
+            let cloze_cards = CardQuery::default().source(self.id).fetch_carditems(conn)?;
             self.clozes = StatefulList::with_items(cloze_cards);
         }
+        Ok(())
     }
-    pub fn keyhandler(&mut self, conn: &Arc<Mutex<Connection>>, key: MyKey) {
+    pub fn keyhandler(&mut self, conn: &Arc<Mutex<Connection>>, key: MyKey) -> Result<()> {
         match key {
             MyKey::Alt('x') => {
-                self.extract(conn);
+                self.extract(conn)?;
                 self.source.set_normal_mode();
             }
             MyKey::Alt('z') => {
-                self.cloze(conn);
+                self.cloze(conn)?;
                 self.source.set_normal_mode();
             }
             MyKey::Esc => {
-                self.update_text(conn);
+                self.update_text(conn)?;
             }
             key => self.source.keyhandler(key),
         }
+        Ok(())
     }
-    pub fn update_text(&self, conn: &Arc<Mutex<Connection>>) {
+    pub fn update_text(&self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
         let text = self.source.return_text();
-        update_inc_text(&conn, text, self.id, &self.source.cursor).unwrap();
+        update_inc_text(conn, text, self.id, &self.source.cursor)?;
+        Ok(())
     }
 }
@@ -1,86 +1,150 @@
+use crate::app::Config;
 use crate::utils::{
-    card::{RecallGrade, Review},
+    card::Review,
+    scheduler::Scheduler,
     sql::{
-        fetch::{get_history, get_stability, load_cards},
-        update::update_strength,
+        fetch::{
+            get_all_histories, get_card_topics, get_history, get_memory_states, get_stability,
+            get_topics, load_cards,
+        },
+        update::{set_memory_states, update_strength},
     },
 };
 
+use crate::error::Result;
 use crate::utils::aliases::*;
 use rusqlite::Connection;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashMap;
+use std::fmt;
+use crate::utils::misc::unix_now;
 
 use super::sql::update::set_stability;
 
 use std::sync::{Arc, Mutex};
 
-fn func(passed: f32, stability: f32) -> f32 {
-    let e = 2.7182_f32;
-    e.powf((0.9_f32).log(e) * passed / stability)
-}
-
 fn time_passed_since_review(review: &Review) -> f32 {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as f32;
+    let now = unix_now() as f32;
     let date = review.date as f32;
     let diff = now - date;
 
     diff / 86400_f32
 }
 
-pub fn calc_strength(conn: &Arc<Mutex<Connection>>) {
-    let cards = load_cards(conn).unwrap();
+pub fn calc_strength(conn: &Arc<Mutex<Connection>>, scheduler: &dyn Scheduler) -> Result<()> {
+    let cards = load_cards(conn)?;
 
     let mut strength;
     let mut passed;
 
     for card in cards.iter() {
-        let history = get_history(conn, card.id).unwrap();
-        if card.is_complete() {
-            let hislen = history.len();
-            if hislen == 0 as usize {
-                panic! {"wtf {}", &card.question};
-            }
-            let stability = get_stability(conn, card.id);
-            passed = time_passed_since_review(&history[(history.len() - 1) as usize]);
-            strength = func(passed, stability);
-            update_strength(conn, card.id, strength).unwrap();
+        if !card.is_complete() {
+            continue;
+        }
+        // a finished card that was never reviewed has nothing to decay from
+        let history = get_history(conn, card.id)?;
+        if let Some(last) = history.last() {
+            let stability = get_stability(conn, card.id)?;
+            passed = time_passed_since_review(last);
+            strength = scheduler.recall(stability, passed);
+            update_strength(conn, card.id, strength)?;
         }
     }
+    Ok(())
 }
 
-pub fn calc_stability(conn: &Arc<Mutex<Connection>>, id: CardID) {
-    let history = get_history(conn, id).unwrap();
-    let hislen = history.len();
-    let grade = &history[hislen - 1].grade;
+pub fn calc_stability(
+    conn: &Arc<Mutex<Connection>>,
+    id: CardID,
+    scheduler: &dyn Scheduler,
+) -> Result<()> {
+    let history = get_history(conn, id)?;
+    if let Some(state) = scheduler.memory_state(&history) {
+        set_stability(conn, id, state.stability)?;
+    }
+    Ok(())
+}
 
-    let gradefactor = match grade {
-        RecallGrade::None => 0.25,
-        RecallGrade::Failed => 0.5,
-        RecallGrade::Decent => 2.,
-        RecallGrade::Easy => 4.,
-    };
+/// Averages over all finished cards before and after a full recompute.
+pub struct Recompute {
+    pub cards: usize,
+    pub changed: usize,
+    pub stability: (f32, f32),
+    pub strength: (f32, f32),
+    /// cards below the default retention, so due right away
+    pub due: (usize, usize),
+}
 
-    if hislen < 2 {
-        set_stability(conn, id, gradefactor).unwrap();
-        return;
+impl fmt::Display for Recompute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "recomputed {} cards, {} changed", self.cards, self.changed)?;
+        writeln!(f, "{:<16} {:>10} {:>10}", "", "before", "after")?;
+        writeln!(
+            f,
+            "{:<16} {:>10.2} {:>10.2}",
+            "mean stability", self.stability.0, self.stability.1
+        )?;
+        writeln!(
+            f,
+            "{:<16} {:>10.3} {:>10.3}",
+            "mean strength", self.strength.0, self.strength.1
+        )?;
+        writeln!(f, "{:<16} {:>10} {:>10}", "due now", self.due.0, self.due.1)
     }
+}
+
+/// Replays the whole history of every finished card through the configured scheduler and
+/// rewrites their stability and strength in one go. Cards without a history are left alone.
+pub fn recompute_all(conn: &Arc<Mutex<Connection>>, config: &Config) -> Result<Recompute> {
+    let scheduler = config.scheduler();
+    let before = get_memory_states(conn)?;
+    let histories: HashMap<CardID, Vec<Review>> =
+        get_all_histories(conn)?.into_iter().collect();
 
-    let prev_stability = get_stability(conn, id);
-    let time_passed = time_passed_since_review(&history[(hislen - 2) as usize]);
+    let after: Vec<(CardID, f32, f32)> = before
+        .iter()
+        .map(|(id, strength, stability)| {
+            let history = match histories.get(id) {
+                Some(history) if !history.is_empty() => history,
+                _ => return (*id, *strength, *stability),
+            };
+            let stability = match scheduler.memory_state(history) {
+                Some(state) => state.stability,
+                None => *stability,
+            };
+            let passed = time_passed_since_review(history.last().unwrap());
+            (*id, scheduler.recall(stability, passed), stability)
+        })
+        .collect();
+    set_memory_states(conn, &after)?;
 
-    let new_stability = if gradefactor < 1. {
-        time_passed * gradefactor
-    } else {
-        if time_passed > prev_stability {
-            time_passed * gradefactor
-        } else {
-            ((prev_stability * gradefactor) - gradefactor) * time_passed / prev_stability
-                + prev_stability
+    let mean = |states: &[(CardID, f32, f32)], pick: fn(&(CardID, f32, f32)) -> f32| {
+        if states.is_empty() {
+            return 0.;
         }
+        states.iter().map(pick).sum::<f32>() / states.len() as f32
     };
-
-    set_stability(conn, id, new_stability).unwrap();
+    // due against the retention of their own topic, like the review queue does
+    let topics = get_topics(conn)?;
+    let card_topics = get_card_topics(conn)?;
+    let retention = |id: &CardID| match card_topics.get(id) {
+        Some(topic) => config.retention(&topics, *topic),
+        None => config.retention,
+    };
+    let due = |states: &[(CardID, f32, f32)]| {
+        states
+            .iter()
+            .filter(|state| state.1 < retention(&state.0))
+            .count()
+    };
+    Ok(Recompute {
+        cards: after.len(),
+        changed: before
+            .iter()
+            .zip(&after)
+            .filter(|(old, new)| (old.2 - new.2).abs() > 1e-3)
+            .count(),
+        stability: (mean(&before, |s| s.2), mean(&after, |s| s.2)),
+        strength: (mean(&before, |s| s.1), mean(&after, |s| s.1)),
+        due: (due(&before), due(&after)),
+    })
 }
@@ -0,0 +1,67 @@
+use std::sync::{Arc, Mutex};
+
+use rusqlite::Connection;
+
+use crate::app::{Config, LeechAction};
+use crate::error::Result;
+use crate::utils::aliases::*;
+use crate::utils::card::{Card, RecallGrade, Review};
+use crate::utils::sql::fetch::{fetch_question, get_history, is_leech, load_leeches};
+use crate::utils::sql::insert::new_leech;
+use crate::utils::sql::tags::add_tag;
+use crate::utils::sql::update::set_suspended;
+
+/// What leeches get tagged with.
+pub const LEECH_TAG: &str = "leech";
+
+/// Times the card was failed after it had already been learned, i.e. not counting
+/// its first review.
+pub fn lapses(history: &[Review]) -> u32 {
+    history
+        .iter()
+        .skip(1)
+        .filter(|review| matches!(review.grade, RecallGrade::None | RecallGrade::Failed))
+        .count() as u32
+}
+
+/// Flags the card as a leech once its lapses reach the threshold, and applies the
+/// configured action. Returns a notice for the user when that happens.
+pub fn check_leech(
+    conn: &Arc<Mutex<Connection>>,
+    id: CardID,
+    config: &Config,
+) -> Result<Option<String>> {
+    let lapses = lapses(&get_history(conn, id)?);
+    if lapses < config.leech_threshold || is_leech(conn, id)? {
+        return Ok(None);
+    }
+    new_leech(conn, id, lapses)?;
+    add_tag(conn, id, LEECH_TAG)?;
+    let consequence = match config.leech_action {
+        LeechAction::Tag => "It's been tagged and added to the list of leeches in the tools tab.",
+        LeechAction::Suspend => {
+            set_suspended(conn, id, true)?;
+            "It's been suspended."
+        }
+        LeechAction::Unfinished => {
+            Card::uncomplete_card(conn, id)?;
+            "It's been turned into an unfinished card, consider rewriting it."
+        }
+    };
+    Ok(Some(format!(
+        "Leech! You've forgotten this card {} times. {}",
+        lapses, consequence
+    )))
+}
+
+pub fn leech_report(conn: &Arc<Mutex<Connection>>) -> Result<String> {
+    let leeches = load_leeches(conn)?;
+    if leeches.is_empty() {
+        return Ok("No leeches\n".to_string());
+    }
+    let mut report = format!("{:>6}  question\n", "lapses");
+    for (id, lapses) in leeches {
+        report.push_str(&format!("{:>6}  {}\n", lapses, fetch_question(conn, id)?));
+    }
+    Ok(report)
+}
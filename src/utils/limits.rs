@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use rusqlite::Connection;
+
+use crate::app::Config;
+use crate::utils::aliases::*;
+use crate::utils::misc::today_start;
+use crate::utils::sql::fetch::{get_topic_of_card, get_topics, new_cards_since, reviews_since};
+use crate::widgets::topics::Topic;
+
+#[derive(Clone, Copy)]
+pub enum Limit {
+    New,
+    Reviews,
+}
+
+#[derive(Clone)]
+struct Counter {
+    global: Option<u32>,
+    topic_limits: Vec<(TopicID, u32)>,
+    today: HashMap<TopicID, u32>,
+}
+
+impl Counter {
+    fn total(&self) -> u32 {
+        self.today.values().sum()
+    }
+
+    fn total_within(&self, topics: &[Topic], ancestor: TopicID) -> u32 {
+        self.today
+            .iter()
+            .filter(|(id, _)| is_within(topics, **id, ancestor))
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    fn allows(&self, topics: &[Topic], topic: TopicID) -> bool {
+        if let Some(max) = self.global {
+            if self.total() >= max {
+                return false;
+            }
+        }
+        self.topic_limits
+            .iter()
+            .filter(|(id, _)| is_within(topics, topic, *id))
+            .all(|(id, max)| self.total_within(topics, *id) < *max)
+    }
+
+    fn add(&mut self, topic: TopicID) {
+        *self.today.entry(topic).or_insert(0) += 1;
+    }
+}
+
+fn is_within(topics: &[Topic], topic: TopicID, ancestor: TopicID) -> bool {
+    let mut id = topic;
+    for _ in 0..=topics.len() {
+        if id == ancestor {
+            return true;
+        }
+        match topics.iter().find(|topic| topic.id == id) {
+            Some(topic) => id = topic.parent,
+            None => return false,
+        }
+    }
+    false
+}
+
+/// Caps on how many new cards and reviews are done per day, counted from the revlog
+/// since local midnight. A cap on a topic is shared by the topic and its subtopics.
+pub struct DailyLimits {
+    topics: Vec<Topic>,
+    new: Counter,
+    reviews: Counter,
+}
+
+impl DailyLimits {
+    pub fn new(conn: &Arc<Mutex<Connection>>, config: &Config) -> rusqlite::Result<Self> {
+        let topics = get_topics(conn)?;
+        let midnight = today_start();
+        let new = Counter {
+            global: config.new_per_day,
+            topic_limits: config.topic_limits(&topics, |topic| topic.new_per_day),
+            today: new_cards_since(conn, midnight)?,
+        };
+        let reviews = Counter {
+            global: config.reviews_per_day,
+            topic_limits: config.topic_limits(&topics, |topic| topic.reviews_per_day),
+            today: reviews_since(conn, midnight)?,
+        };
+        Ok(Self {
+            topics,
+            new,
+            reviews,
+        })
+    }
+
+    fn counter(&self, kind: Limit) -> &Counter {
+        match kind {
+            Limit::New => &self.new,
+            Limit::Reviews => &self.reviews,
+        }
+    }
+
+    /// Keeps the cards, in order, that fit within what's left of today's limits.
+    pub fn select(
+        &self,
+        conn: &Arc<Mutex<Connection>>,
+        kind: Limit,
+        cards: Vec<CardID>,
+    ) -> rusqlite::Result<Vec<CardID>> {
+        let mut planned = self.counter(kind).clone();
+        let mut selected = Vec::with_capacity(cards.len());
+        for id in cards {
+            let topic = get_topic_of_card(conn, id)?;
+            if planned.allows(&self.topics, topic) {
+                planned.add(topic);
+                selected.push(id);
+            }
+        }
+        Ok(selected)
+    }
+
+    pub fn add(&mut self, kind: Limit, topic: TopicID) {
+        match kind {
+            Limit::New => self.new.add(topic),
+            Limit::Reviews => self.reviews.add(topic),
+        }
+    }
+
+    pub fn remove(&mut self, kind: Limit, topic: TopicID) {
+        let counter = match kind {
+            Limit::New => &mut self.new,
+            Limit::Reviews => &mut self.reviews,
+        };
+        if let Some(count) = counter.today.get_mut(&topic) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    /// Done today, and the global cap if there is one.
+    pub fn status(&self, kind: Limit) -> String {
+        let counter = self.counter(kind);
+        match counter.global {
+            Some(max) => format!("{}/{}", counter.total(), max),
+            None => counter.total().to_string(),
+        }
+    }
+}
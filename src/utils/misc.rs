@@ -2,6 +2,7 @@ use std::{
     io::BufReader,
     path::PathBuf,
     sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use crate::{app::Audio, tabs::review::logic::ReviewMode, widgets::cardlist::CardItem};
@@ -16,6 +17,7 @@ pub fn modecolor(mode: &ReviewMode) -> Color {
         ReviewMode::Review(_) => Color::Red,
         ReviewMode::Unfinished(_) => Color::Yellow,
         ReviewMode::Pending(_) => Color::Cyan,
+        ReviewMode::Learning(..) => Color::Magenta,
         ReviewMode::IncRead(_) => Color::Green,
         ReviewMode::Done => Color::Blue,
     }
@@ -27,12 +29,26 @@ pub enum PopUpStatus {
     Finished,
 }
 
+/// Unix time of the last local midnight, where "today" starts for daily counts.
+pub fn today_start() -> u32 {
+    chrono::Local::today().and_hms(0, 0, 0).timestamp() as u32
+}
+
+/// The current time as a unix timestamp.
+pub fn unix_now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32
+}
+
 pub fn play_audio(audio: &Option<Audio>, path: PathBuf) {
     if let Ok(file) = std::fs::File::open(path) {
         if let Some(audio) = audio {
-            let beep1 = audio.handle.play_once(BufReader::new(file)).unwrap();
-            beep1.set_volume(0.2);
-            beep1.detach();
+            if let Ok(beep1) = audio.handle.play_once(BufReader::new(file)) {
+                beep1.set_volume(0.2);
+                beep1.detach();
+            }
         }
     }
 }
@@ -89,7 +105,7 @@ where
 
     Layout::default()
         .direction(direction)
-        .constraints(constraintvec.as_ref())
+        .constraints(constraintvec)
         .split(area)
 }
 
@@ -116,9 +132,14 @@ use hyper_tls::HttpsConnector;
 use serde_derive::{Deserialize, Serialize};
 
 use super::{aliases::CardID, sql::fetch::fetch_card, statelist::StatefulList};
+use crate::error::{Result, SpekiError};
+
+fn openai_error(err: impl std::fmt::Display) -> SpekiError {
+    SpekiError::Other(format!("Error calling OpenAI: {}", err))
+}
 
 #[tokio::main]
-pub async fn get_gpt3_response(api_key: &str, user_input: &str) -> String {
+pub async fn get_gpt3_response(api_key: &str, user_input: &str) -> Result<String> {
     // Check for environment variable OPENAI_KEY
 
     let https = HttpsConnector::new();
@@ -137,61 +158,63 @@ pub async fn get_gpt3_response(api_key: &str, user_input: &str) -> String {
         stop,
     };
 
-    let body = Body::from(serde_json::to_vec(&openai_request).unwrap());
+    let body = Body::from(serde_json::to_vec(&openai_request).map_err(openai_error)?);
 
     let req = Request::post(uri)
         .header(header::CONTENT_TYPE, "application/json")
         .header("Authorization", &auth_header_val)
         .body(body)
-        .unwrap();
+        .map_err(openai_error)?;
 
-    let res = client.request(req).await.unwrap();
+    let res = client.request(req).await.map_err(openai_error)?;
 
-    let body = hyper::body::aggregate(res).await.unwrap();
+    let body = hyper::body::aggregate(res).await.map_err(openai_error)?;
 
-    let json: OpenAIResponse = match serde_json::from_reader(body.reader()) {
-        Ok(response) => response,
-        Err(_) => {
-            println!("Error calling OpenAI. Check environment variable OPENAI_KEY");
-            std::process::exit(1);
-        }
-    };
+    let json: OpenAIResponse = serde_json::from_reader(body.reader())
+        .map_err(|_| openai_error("check the gpt3 key in the config file"))?;
 
-    json.choices[0]
+    let choice = json.choices.first().ok_or_else(|| openai_error("no answer"))?;
+    Ok(choice
         .text
         .split('\n')
         .map(|s| s.trim())
         .filter(|s| !s.is_empty())
         .collect::<Vec<_>>()
-        .join("\n")
+        .join("\n"))
 }
 
-pub fn get_dependencies(conn: &Arc<Mutex<Connection>>, id: CardID) -> StatefulList<CardItem> {
-    let thecard = fetch_card(conn, id);
+pub fn get_dependencies(
+    conn: &Arc<Mutex<Connection>>,
+    id: CardID,
+) -> rusqlite::Result<StatefulList<CardItem>> {
+    let thecard = fetch_card(conn, id)?;
     let dep_ids = &thecard.dependencies;
     let mut depvec: Vec<CardItem> = vec![];
 
     for dep in dep_ids {
-        let card = fetch_card(conn, *dep);
+        let card = fetch_card(conn, *dep)?;
         depvec.push(CardItem {
             question: card.question,
             id: *dep,
         });
     }
-    StatefulList::with_items(depvec)
+    Ok(StatefulList::with_items(depvec))
 }
 
-pub fn get_dependents(conn: &Arc<Mutex<Connection>>, id: CardID) -> StatefulList<CardItem> {
-    let thecard = fetch_card(conn, id);
+pub fn get_dependents(
+    conn: &Arc<Mutex<Connection>>,
+    id: CardID,
+) -> rusqlite::Result<StatefulList<CardItem>> {
+    let thecard = fetch_card(conn, id)?;
     let dep_ids = &thecard.dependents;
     let mut depvec: Vec<CardItem> = vec![];
 
     for dep in dep_ids {
-        let card = fetch_card(conn, *dep);
+        let card = fetch_card(conn, *dep)?;
         depvec.push(CardItem {
             question: card.question,
             id: *dep,
         });
     }
-    StatefulList::with_items(depvec)
+    Ok(StatefulList::with_items(depvec))
 }
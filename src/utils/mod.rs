@@ -1,8 +1,17 @@
 pub mod aliases;
+pub mod backup;
 pub mod card;
+pub mod check;
+pub mod forecast;
 pub mod incread;
 pub mod interval;
+pub mod leech;
+pub mod limits;
 pub mod misc;
+pub mod optimizer;
+pub mod order;
+pub mod scheduler;
+pub mod siblings;
 pub mod sql;
 pub mod statelist;
 
@@ -0,0 +1,145 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use rusqlite::Connection;
+
+use crate::app::Config;
+use crate::utils::card::{RecallGrade, Review};
+use crate::utils::scheduler::{days_between, Scheduler};
+use crate::utils::sql::fetch::get_all_histories;
+
+const MAX_ROUNDS: usize = 200;
+const MIN_STEP: f32 = 0.001;
+
+pub struct OptimizeResult {
+    pub scheduler: &'static str,
+    pub reviews: usize,
+    pub loss_before: f64,
+    pub loss_after: f64,
+    pub parameters: Vec<f32>,
+}
+
+impl std::fmt::Display for OptimizeResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let params = self
+            .parameters
+            .iter()
+            .map(|param| format!("{:.4}", param))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(
+            f,
+            "Fitted {} scheduler on {} reviews\nlog-loss before: {:.4}\nlog-loss after:  {:.4}\nparameters: [{}]",
+            self.scheduler, self.reviews, self.loss_before, self.loss_after, params
+        )
+    }
+}
+
+/// Mean log-loss of the scheduler's recall predictions over every review that
+/// follows an earlier one, where anything above "failed" counts as recalled.
+pub fn log_loss(scheduler: &dyn Scheduler, histories: &[Vec<Review>]) -> (f64, usize) {
+    let mut loss = 0.;
+    let mut count = 0;
+    for history in histories {
+        let mut state = match history.first() {
+            Some(first) => scheduler.first_state(first),
+            None => continue,
+        };
+        for pair in history.windows(2) {
+            let days = days_between(&pair[0], &pair[1]);
+            let recall = (scheduler.recall(state.stability, days) as f64).clamp(0.0001, 0.9999);
+            let recalled = matches!(pair[1].grade, RecallGrade::Decent | RecallGrade::Easy);
+            loss -= if recalled { recall.ln() } else { (1. - recall).ln() };
+            count += 1;
+            state = scheduler.next_state(&state, &pair[0], &pair[1]);
+        }
+    }
+    if count == 0 {
+        return (0., 0);
+    }
+    (loss / count as f64, count)
+}
+
+/// Fits the scheduler's parameters to the review log by minimizing log-loss, using a
+/// pattern search that stays within the scheduler's parameter bounds.
+pub fn optimize(scheduler: &dyn Scheduler, histories: &[Vec<Review>]) -> OptimizeResult {
+    let bounds = scheduler.parameter_bounds();
+    let mut params = scheduler.parameters();
+    let (loss_before, reviews) = log_loss(scheduler, histories);
+    let mut best = loss_before;
+    let mut steps: Vec<f32> = bounds.iter().map(|(low, high)| (high - low) / 10.).collect();
+
+    if reviews > 0 {
+        for _ in 0..MAX_ROUNDS {
+            let mut improved = false;
+            for i in 0..params.len() {
+                for direction in [1., -1.] {
+                    let mut candidate = params.clone();
+                    candidate[i] = (params[i] + direction * steps[i]).clamp(bounds[i].0, bounds[i].1);
+                    if candidate[i] == params[i] {
+                        continue;
+                    }
+                    let (loss, _) = log_loss(&*scheduler.with_parameters(&candidate), histories);
+                    if loss < best {
+                        best = loss;
+                        params = candidate;
+                        improved = true;
+                        break;
+                    }
+                }
+            }
+            if !improved {
+                steps.iter_mut().for_each(|step| *step /= 2.);
+                let smallest = bounds
+                    .iter()
+                    .zip(&steps)
+                    .all(|((low, high), step)| *step < (high - low) * MIN_STEP);
+                if smallest {
+                    break;
+                }
+            }
+        }
+    }
+
+    OptimizeResult {
+        scheduler: scheduler.name(),
+        reviews,
+        loss_before,
+        loss_after: best,
+        parameters: params,
+    }
+}
+
+pub fn optimize_revlog(
+    conn: &Arc<Mutex<Connection>>,
+    scheduler: &dyn Scheduler,
+) -> rusqlite::Result<OptimizeResult> {
+    let histories: Vec<Vec<Review>> = get_all_histories(conn)?
+        .into_iter()
+        .map(|(_, history)| history)
+        .collect();
+    Ok(optimize(scheduler, &histories))
+}
+
+/// Fits the configured scheduler and saves the result to the config file. Returns a
+/// report for the user.
+pub fn optimize_and_save(
+    conn: &Arc<Mutex<Connection>>,
+    config: &mut Config,
+    path: &Path,
+) -> rusqlite::Result<String> {
+    let result = optimize_revlog(conn, &*config.scheduler())?;
+    Ok(save_result(&result, config, path))
+}
+
+/// Saves the fitted parameters to the config file, unless there was nothing to fit them to.
+/// Returns a report for the user.
+pub fn save_result(result: &OptimizeResult, config: &mut Config, path: &Path) -> String {
+    if result.reviews == 0 {
+        return "Not enough reviews to fit the scheduler yet".to_string();
+    }
+    match config.save_parameters(path, &result.parameters) {
+        Ok(_) => format!("{}\n\nParameters saved to {}", result, path.display()),
+        Err(e) => format!("{}\n\nFailed to save parameters: {}", result, e),
+    }
+}
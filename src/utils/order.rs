@@ -0,0 +1,154 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use rand::prelude::*;
+use rusqlite::Connection;
+use serde_derive::Deserialize;
+
+use crate::app::Config;
+use crate::error::Result;
+use crate::utils::aliases::*;
+use crate::utils::sql::fetch::{
+    get_card_state, get_dependencies, get_history, get_stability, get_strength,
+    get_topic_of_card, get_topics,
+};
+use crate::utils::scheduler::{due_after, Scheduler};
+use crate::widgets::topics::{Topic, TopicList};
+
+/// The order cards are shown in during a review session.
+#[derive(Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewOrder {
+    #[default]
+    Random,
+    /// Lowest strength first.
+    Weakest,
+    /// Topic by topic, in the order of the topic tree.
+    Topic,
+    /// Prerequisites before the cards that depend on them.
+    Dependencies,
+    /// The cards that have been due the longest first.
+    Oldest,
+}
+
+impl fmt::Display for ReviewOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ReviewOrder::Random => "random",
+            ReviewOrder::Weakest => "weakest first",
+            ReviewOrder::Topic => "by topic",
+            ReviewOrder::Dependencies => "dependencies first",
+            ReviewOrder::Oldest => "oldest due first",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl ReviewOrder {
+    pub fn next(self) -> Self {
+        match self {
+            ReviewOrder::Random => ReviewOrder::Weakest,
+            ReviewOrder::Weakest => ReviewOrder::Topic,
+            ReviewOrder::Topic => ReviewOrder::Dependencies,
+            ReviewOrder::Dependencies => ReviewOrder::Oldest,
+            ReviewOrder::Oldest => ReviewOrder::Random,
+        }
+    }
+
+    pub fn sort(
+        self,
+        conn: &Arc<Mutex<Connection>>,
+        config: &Config,
+        cards: &mut Vec<CardID>,
+    ) -> Result<()> {
+        match self {
+            ReviewOrder::Random => cards.shuffle(&mut thread_rng()),
+            ReviewOrder::Weakest => {
+                cards.sort_by_cached_key(|id| match get_strength(conn, *id) {
+                    Ok(strength) => (0, (strength * 1e6) as i64),
+                    Err(_) => (1, 0),
+                })
+            }
+            ReviewOrder::Topic => {
+                let position: HashMap<TopicID, usize> = TopicList::new(conn)
+                    .items
+                    .iter()
+                    .enumerate()
+                    .map(|(pos, topic)| (topic.id, pos))
+                    .collect();
+                let mut keyed = Vec::with_capacity(cards.len());
+                for id in cards.iter() {
+                    let topic = get_topic_of_card(conn, *id)?;
+                    keyed.push((position.get(&topic).copied().unwrap_or(usize::MAX), *id));
+                }
+                keyed.sort_unstable();
+                *cards = keyed.into_iter().map(|(_, id)| id).collect();
+            }
+            ReviewOrder::Dependencies => *cards = topological(conn, cards)?,
+            ReviewOrder::Oldest => {
+                let topics = get_topics(conn)?;
+                let scheduler = config.scheduler();
+                let mut keyed = Vec::with_capacity(cards.len());
+                for id in cards.iter() {
+                    keyed.push((due_since(conn, config, &topics, &*scheduler, *id)?, *id));
+                }
+                keyed.sort_by_key(|(due, _)| *due);
+                *cards = keyed.into_iter().map(|(_, id)| id).collect();
+            }
+        }
+        Ok(())
+    }
+}
+
+fn due_since(
+    conn: &Arc<Mutex<Connection>>,
+    config: &Config,
+    topics: &[Topic],
+    scheduler: &dyn Scheduler,
+    id: CardID,
+) -> Result<u32> {
+    let state = get_card_state(conn, id)?;
+    if let Some((skiptime, skipduration)) = state.unfinished {
+        // skipduration doubles with every skip, so it can grow past what fits
+        return Ok(skiptime.saturating_add(skipduration.saturating_mul(86400)));
+    }
+    let last = match get_history(conn, id)?.last() {
+        Some(review) => review.date,
+        None => return Ok(u32::MAX),
+    };
+    let retention = config.retention(topics, get_topic_of_card(conn, id)?);
+    let interval = scheduler.interval(get_stability(conn, id)?, retention);
+    Ok(due_after(last, interval))
+}
+
+// depth first, so every card comes after the ones among `cards` that it depends on.
+// a dependency cycle is broken wherever it's first entered
+fn topological(conn: &Arc<Mutex<Connection>>, cards: &[CardID]) -> Result<Vec<CardID>> {
+    let wanted: HashSet<CardID> = cards.iter().copied().collect();
+    let mut visited = HashSet::new();
+    let mut sorted = Vec::with_capacity(cards.len());
+    for id in cards {
+        visit(conn, *id, &wanted, &mut visited, &mut sorted)?;
+    }
+    Ok(sorted)
+}
+
+fn visit(
+    conn: &Arc<Mutex<Connection>>,
+    id: CardID,
+    wanted: &HashSet<CardID>,
+    visited: &mut HashSet<CardID>,
+    sorted: &mut Vec<CardID>,
+) -> Result<()> {
+    if !visited.insert(id) {
+        return Ok(());
+    }
+    for dependency in get_dependencies(conn, id)? {
+        if wanted.contains(&dependency) {
+            visit(conn, dependency, wanted, visited, sorted)?;
+        }
+    }
+    sorted.push(id);
+    Ok(())
+}
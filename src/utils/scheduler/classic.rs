@@ -0,0 +1,105 @@
+use super::{days_between, MemoryState, Scheduler};
+use crate::utils::card::{RecallGrade, Review};
+use serde_derive::{Deserialize, Serialize};
+
+/// The original speki model: exponential forgetting where stability is the
+/// amount of days until recall drops to `decay`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct ClassicParams {
+    pub decay: f32,
+    pub grade_factors: [f32; 4],
+}
+
+impl Default for ClassicParams {
+    fn default() -> Self {
+        Self {
+            decay: 0.9,
+            grade_factors: [0.25, 0.5, 2., 4.],
+        }
+    }
+}
+
+impl ClassicParams {
+    /// Inverse of `Scheduler::parameters`.
+    pub fn from_slice(params: &[f32]) -> Self {
+        Self {
+            decay: params[0],
+            grade_factors: [params[1], params[2], params[3], params[4]],
+        }
+    }
+}
+
+pub struct Classic {
+    params: ClassicParams,
+}
+
+impl Classic {
+    pub fn new(params: ClassicParams) -> Self {
+        Self { params }
+    }
+
+    fn gradefactor(&self, grade: &RecallGrade) -> f32 {
+        match grade {
+            RecallGrade::None => self.params.grade_factors[0],
+            RecallGrade::Failed => self.params.grade_factors[1],
+            RecallGrade::Decent => self.params.grade_factors[2],
+            RecallGrade::Easy => self.params.grade_factors[3],
+        }
+    }
+}
+
+impl Scheduler for Classic {
+    fn name(&self) -> &'static str {
+        "classic"
+    }
+
+    fn first_state(&self, first: &Review) -> MemoryState {
+        MemoryState {
+            stability: self.gradefactor(&first.grade),
+            difficulty: 0.,
+        }
+    }
+
+    fn next_state(&self, state: &MemoryState, previous: &Review, review: &Review) -> MemoryState {
+        let stability = state.stability;
+        let gradefactor = self.gradefactor(&review.grade);
+        let time_passed = days_between(previous, review);
+
+        let stability = if gradefactor < 1. || time_passed > stability {
+            time_passed * gradefactor
+        } else {
+            ((stability * gradefactor) - gradefactor) * time_passed / stability + stability
+        };
+
+        MemoryState {
+            stability,
+            difficulty: 0.,
+        }
+    }
+
+    fn recall(&self, stability: f32, days: f32) -> f32 {
+        let e = std::f32::consts::E;
+        e.powf(self.params.decay.ln() * days / stability)
+    }
+
+    fn interval(&self, stability: f32, retention: f32) -> f32 {
+        stability * retention.ln() / self.params.decay.ln()
+    }
+
+    fn parameters(&self) -> Vec<f32> {
+        let mut params = vec![self.params.decay];
+        params.extend(self.params.grade_factors);
+        params
+    }
+
+    // failing grades must stay below 1 and passing grades above, as that's how
+    // memory_state tells them apart
+    fn parameter_bounds(&self) -> Vec<(f32, f32)> {
+        vec![(0.5, 0.99), (0.01, 0.99), (0.01, 0.99), (1.01, 10.), (1.01, 20.)]
+    }
+
+    fn with_parameters(&self, params: &[f32]) -> Box<dyn Scheduler> {
+        Box::new(Classic::new(ClassicParams::from_slice(params)))
+    }
+}
@@ -0,0 +1,137 @@
+use super::{days_between, MemoryState, Scheduler};
+use crate::utils::card::{RecallGrade, Review};
+use serde_derive::{Deserialize, Serialize};
+
+/// Free Spaced Repetition Scheduler (v4). Tracks difficulty alongside stability and
+/// uses a power forgetting curve.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(default)]
+pub struct FsrsParams {
+    pub weights: [f32; 17],
+}
+
+impl Default for FsrsParams {
+    fn default() -> Self {
+        Self {
+            weights: [
+                0.4, 0.6, 2.4, 5.8, 4.93, 0.94, 0.86, 0.01, 1.49, 0.14, 0.94, 2.18, 0.05, 0.34,
+                1.26, 0.29, 2.61,
+            ],
+        }
+    }
+}
+
+impl FsrsParams {
+    /// Inverse of `Scheduler::parameters`.
+    pub fn from_slice(params: &[f32]) -> Self {
+        let mut weights = [0.; 17];
+        weights.copy_from_slice(params);
+        Self { weights }
+    }
+}
+
+pub struct Fsrs {
+    w: [f32; 17],
+}
+
+impl Fsrs {
+    pub fn new(params: FsrsParams) -> Self {
+        Self { w: params.weights }
+    }
+
+    // fsrs rates from 1 (again) to 4 (easy), both of our failing grades count as "again"
+    fn rating(grade: &RecallGrade) -> f32 {
+        match grade {
+            RecallGrade::None | RecallGrade::Failed => 1.,
+            RecallGrade::Decent => 3.,
+            RecallGrade::Easy => 4.,
+        }
+    }
+
+    fn init_difficulty(&self, rating: f32) -> f32 {
+        (self.w[4] - (rating - 3.) * self.w[5]).clamp(1., 10.)
+    }
+
+    fn next_difficulty(&self, difficulty: f32, rating: f32) -> f32 {
+        let next = difficulty - self.w[6] * (rating - 3.);
+        let reverted = self.w[7] * self.init_difficulty(3.) + (1. - self.w[7]) * next;
+        reverted.clamp(1., 10.)
+    }
+
+    fn next_stability(&self, state: &MemoryState, recall: f32, rating: f32) -> f32 {
+        let (s, d, w) = (state.stability, state.difficulty, &self.w);
+        if rating < 2. {
+            return w[11] * d.powf(-w[12]) * ((s + 1.).powf(w[13]) - 1.) * (w[14] * (1. - recall)).exp();
+        }
+        let hard_penalty = if rating == 2. { w[15] } else { 1. };
+        let easy_bonus = if rating == 4. { w[16] } else { 1. };
+        s * (w[8].exp()
+            * (11. - d)
+            * s.powf(-w[9])
+            * ((w[10] * (1. - recall)).exp() - 1.)
+            * hard_penalty
+            * easy_bonus
+            + 1.)
+    }
+}
+
+impl Scheduler for Fsrs {
+    fn name(&self) -> &'static str {
+        "fsrs"
+    }
+
+    fn first_state(&self, first: &Review) -> MemoryState {
+        let rating = Self::rating(&first.grade);
+        MemoryState {
+            stability: self.w[rating as usize - 1],
+            difficulty: self.init_difficulty(rating),
+        }
+    }
+
+    fn next_state(&self, state: &MemoryState, previous: &Review, review: &Review) -> MemoryState {
+        let rating = Self::rating(&review.grade);
+        let recall = self.recall(state.stability, days_between(previous, review));
+        MemoryState {
+            stability: self.next_stability(state, recall, rating).max(0.01),
+            difficulty: self.next_difficulty(state.difficulty, rating),
+        }
+    }
+
+    fn recall(&self, stability: f32, days: f32) -> f32 {
+        (1. + days.max(0.) / (9. * stability)).powi(-1)
+    }
+
+    fn interval(&self, stability: f32, retention: f32) -> f32 {
+        9. * stability * (1. / retention - 1.)
+    }
+
+    fn parameters(&self) -> Vec<f32> {
+        self.w.to_vec()
+    }
+
+    fn parameter_bounds(&self) -> Vec<(f32, f32)> {
+        vec![
+            (0.1, 100.),
+            (0.1, 100.),
+            (0.1, 100.),
+            (0.1, 100.),
+            (1., 10.),
+            (0.1, 5.),
+            (0.1, 5.),
+            (0., 0.75),
+            (0., 4.),
+            (0., 0.8),
+            (0.01, 3.),
+            (0.1, 5.),
+            (0.01, 0.2),
+            (0.01, 0.9),
+            (0.01, 3.),
+            (0., 1.),
+            (1., 6.),
+        ]
+    }
+
+    fn with_parameters(&self, params: &[f32]) -> Box<dyn Scheduler> {
+        Box::new(Fsrs::new(FsrsParams::from_slice(params)))
+    }
+}
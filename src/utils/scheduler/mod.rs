@@ -0,0 +1,93 @@
+pub mod classic;
+pub mod fsrs;
+
+use crate::utils::card::Review;
+use serde_derive::Deserialize;
+
+use self::classic::{Classic, ClassicParams};
+use self::fsrs::{Fsrs, FsrsParams};
+
+/// What a scheduler knows about a card after replaying its review history.
+#[derive(Clone, Debug)]
+pub struct MemoryState {
+    pub stability: f32,
+    pub difficulty: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    pub state: MemoryState,
+    pub due: u32,
+}
+
+/// A memory model. Implementations turn the review history of a card (oldest first)
+/// into its memory state, and say how fast that memory decays.
+pub trait Scheduler {
+    fn name(&self) -> &'static str;
+
+    /// The memory state right after the first review of a card.
+    fn first_state(&self, first: &Review) -> MemoryState;
+
+    /// The memory state after `review`, given the state after the `previous` one.
+    fn next_state(&self, state: &MemoryState, previous: &Review, review: &Review) -> MemoryState;
+
+    /// Replays the whole history. Returns None if the card has never been reviewed.
+    fn memory_state(&self, history: &[Review]) -> Option<MemoryState> {
+        let mut state = self.first_state(history.first()?);
+        for pair in history.windows(2) {
+            state = self.next_state(&state, &pair[0], &pair[1]);
+        }
+        Some(state)
+    }
+
+    /// Probability of recalling a card with the given stability after `days` days.
+    fn recall(&self, stability: f32, days: f32) -> f32;
+
+    /// Days after a review until recall probability drops to `retention`.
+    fn interval(&self, stability: f32, retention: f32) -> f32;
+
+    /// The tunable parameters of the model, as a flat list.
+    fn parameters(&self) -> Vec<f32>;
+
+    /// Lower and upper bound for every parameter, used by the optimizer.
+    fn parameter_bounds(&self) -> Vec<(f32, f32)>;
+
+    fn with_parameters(&self, params: &[f32]) -> Box<dyn Scheduler>;
+
+    fn schedule(&self, history: &[Review], retention: f32) -> Option<Schedule> {
+        let state = self.memory_state(history)?;
+        let last = history.last()?;
+        let due = due_after(last.date, self.interval(state.stability, retention));
+        Some(Schedule { state, due })
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SchedulerKind {
+    #[default]
+    Classic,
+    Fsrs,
+}
+
+pub fn new_scheduler(
+    kind: SchedulerKind,
+    classic: &ClassicParams,
+    fsrs: &FsrsParams,
+) -> Box<dyn Scheduler> {
+    match kind {
+        SchedulerKind::Classic => Box::new(Classic::new(classic.clone())),
+        SchedulerKind::Fsrs => Box::new(Fsrs::new(fsrs.clone())),
+    }
+}
+
+/// When a card last reviewed at `last` is due, `days` after it. Intervals too long for a
+/// timestamp make it due at the end of time, negative and NaN ones right away.
+pub fn due_after(last: u32, days: f32) -> u32 {
+    let seconds = (days * 86400.).clamp(0., u32::MAX as f32);
+    last.saturating_add(seconds as u32)
+}
+
+pub fn days_between(earlier: &Review, later: &Review) -> f32 {
+    (later.date as f32 - earlier.date as f32) / 86400.
+}
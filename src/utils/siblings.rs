@@ -0,0 +1,83 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use rusqlite::Connection;
+
+use crate::error::Result;
+use crate::utils::aliases::*;
+use crate::utils::misc::today_start;
+use crate::utils::sql::fetch::{cards_reviewed_since, fetch_card, get_anki_note};
+
+/// What makes two cards siblings: being made from the same incremental reading source,
+/// or being imported from the same anki note.
+#[derive(Hash, PartialEq, Eq, Clone, Copy)]
+enum Sibling {
+    Source(IncID),
+    Note(NoteID),
+}
+
+fn siblings_of(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<Vec<Sibling>> {
+    let mut siblings = vec![];
+    let source = fetch_card(conn, id)?.source;
+    if source != 0 {
+        siblings.push(Sibling::Source(source));
+    }
+    if let Some(note) = get_anki_note(conn, id)? {
+        siblings.push(Sibling::Note(note));
+    }
+    Ok(siblings)
+}
+
+/// Keeps siblings from cueing each other by letting only one of them be shown per day.
+pub struct SiblingBury {
+    /// Which cards of every sibling group have been shown today. A card never gets buried
+    /// because of itself, so one that's due again after being reviewed today stays.
+    shown: HashMap<Sibling, HashSet<CardID>>,
+}
+
+impl SiblingBury {
+    /// Starts out with every card that was already reviewed today.
+    pub fn new(conn: &Arc<Mutex<Connection>>) -> Result<Self> {
+        let mut bury = Self {
+            shown: HashMap::new(),
+        };
+        for id in cards_reviewed_since(conn, today_start())? {
+            let siblings = siblings_of(conn, id)?;
+            bury.show(id, siblings);
+        }
+        Ok(bury)
+    }
+
+    fn show(&mut self, id: CardID, siblings: Vec<Sibling>) {
+        for sibling in siblings {
+            self.shown.entry(sibling).or_default().insert(id);
+        }
+    }
+
+    fn sibling_shown(&self, id: CardID, siblings: &[Sibling]) -> bool {
+        siblings.iter().any(|sibling| {
+            self.shown
+                .get(sibling)
+                .is_some_and(|ids| ids.iter().any(|other| *other != id))
+        })
+    }
+
+    /// Keeps the first card of every sibling group that hasn't been shown today, the
+    /// rest are postponed to tomorrow.
+    pub fn bury(
+        &mut self,
+        conn: &Arc<Mutex<Connection>>,
+        cards: Vec<CardID>,
+    ) -> Result<Vec<CardID>> {
+        let mut kept = Vec::with_capacity(cards.len());
+        for id in cards {
+            let siblings = siblings_of(conn, id)?;
+            if self.sibling_shown(id, &siblings) {
+                continue;
+            }
+            self.show(id, siblings);
+            kept.push(id);
+        }
+        Ok(kept)
+    }
+}
@@ -1,57 +1,77 @@
 use rusqlite::{Connection, params, Result};
 use std::sync::{Mutex, Arc};
 use crate::utils::aliases::*;
+use crate::utils::card::Card;
+use crate::utils::sql::fetch::get_dependents;
+use crate::utils::sql::trash::{trash_card, trash_source, trash_topic};
 
-pub fn delete_topic(conn: &Arc<Mutex<Connection>>, id: TopicID) -> Result<()> {
+/// Moves the topic to the trash, `children` are its subtopics that the caller already moved
+/// up to its parent.
+pub fn delete_topic(conn: &Arc<Mutex<Connection>>, id: TopicID, children: &[TopicID]) -> Result<()> {
+    trash_topic(conn, id, children)
+}
+
+/// Moves the text to the trash.
+pub fn remove_source(conn: &Arc<Mutex<Connection>>, id: IncID) -> Result<()> {
+    trash_source(conn, id)
+}
+
+
+pub fn remove_unfinished(conn: &Connection, id: CardID) -> Result<()> {
     conn
-        .lock()
-        .unwrap()
-        .prepare("delete from topics where id = ?")?
+        .prepare("delete from unfinished_cards where id = ?")?
         .execute(params![id])?;
     Ok(())
 }
 
 
-pub fn remove_unfinished(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<()> {
+pub fn remove_finished(conn: &Connection, id: CardID) -> Result<()> {
     conn
-        .lock()
-        .unwrap()
-        .prepare("delete from unfinished_cards where id = ?")?
+        .prepare("delete from finished_cards where id = ?")?
         .execute(params![id])?;
     Ok(())
 }
 
 
-pub fn remove_pending(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<()> {
+pub fn remove_pending(conn: &Connection, id: CardID) -> Result<()> {
     conn
-        .lock()
-        .unwrap()
         .prepare("delete from pending_cards where id = ?")?
         .execute(params![id])?;
     Ok(())
 }
 
 
-pub fn remove_card(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<()> {
+/// Moves the card to the trash. Everything else that refers to the card goes with it, and
+/// its dependents might not be waiting on anything anymore.
+pub fn remove_card(conn: &Arc<Mutex<Connection>>, id: CardID) -> crate::error::Result<()> {
+    let dependents = get_dependents(conn, id)?;
+    trash_card(conn, id)?;
+    for dependent in dependents {
+        Card::check_resolved(dependent, conn)?;
+    }
+    Ok(())
+}
+
+pub fn remove_revlog(conn: &Arc<Mutex<Connection>>, rowid: RevlogID) -> Result<()> {
     conn
         .lock()
         .unwrap()
-        .prepare("delete from cards where id = ?")?
-        .execute(params![id])?;
-   conn
-        .lock()
-        .unwrap()
-        .prepare("delete from pending_cards where id = ?")?
-        .execute(params![id])?;
-   conn
-        .lock()
-        .unwrap()
-        .prepare("delete from unfinished_cards where id = ?")?
-        .execute(params![id])?;
-   conn
-        .lock()
-        .unwrap()
-        .prepare("delete from finished_cards where id = ?")?
-        .execute(params![id])?;
+        .prepare("delete from revlog where rowid = ?")?
+        .execute(params![rowid])?;
+    Ok(())
+}
+
+pub fn remove_dependency(conn: &Connection, dependent: CardID, dependency: CardID) -> Result<()> {
+    conn
+        .prepare("delete from dependencies where dependent = ? and dependency = ?")?
+        .execute(params![dependent, dependency])?;
+    Ok(())
+}
+
+/// Deletes a row by rowid, `table` has to come from the schema and not from the user.
+pub fn remove_row(conn: &Connection, table: &str, rowid: i64) -> Result<()> {
+    conn
+        .prepare(&format!("delete from {} where rowid = ?", table))?
+        .execute(params![rowid])?;
     Ok(())
 }
@@ -1,11 +1,14 @@
 use crate::utils::aliases::*;
-use crate::utils::card::{Card, RecallGrade, Review}; //, Topic, Review}
+use crate::utils::card::{Card, CardState, RecallGrade, Review}; //, Topic, Review}
+use crate::utils::sql::tags::get_tags;
 use crate::widgets::load_cards::MediaContents;
 use crate::widgets::topics::Topic;
-use rusqlite::{Connection, Result, Row};
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Result, Row};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::utils::misc::unix_now;
 
 enum CardFilter {
     Suspended(bool),
@@ -15,9 +18,12 @@ enum CardFilter {
     Pending(bool),
     Cardtype(CardType),
     StrengthRange((f32, f32)),
+    BelowRetention(Vec<(TopicID, f32)>, f32),
     Minstability(u32),
     Maxstability(u32),
     Contains(String),
+    /// A full-text search, already turned into an FTS5 query by `fts_query`.
+    Search(String),
     Topics(Vec<TopicID>),
     MinPosition(u32),
     MaxPosition(u32),
@@ -25,15 +31,29 @@ enum CardFilter {
     MaxSkipDaysPassed(f32),
     Source(u32),
     DueUnfinished,
-}
-
-use std::fmt;
-impl fmt::Display for CardFilter {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    CreatedAfter(u32),
+    CreatedBefore(u32),
+    ModifiedAfter(u32),
+    ModifiedBefore(u32),
+    Tagged(String),
+    NotTagged(String),
+}
+
+impl CardFilter {
+    /// The condition for the WHERE clause, with every value left as a placeholder and
+    /// pushed onto `params` in order.
+    fn clause(&self, params: &mut Vec<Value>) -> String {
         use CardFilter::*;
-        let text = match self {
-            Suspended(val) => format!("suspended = {}", val),
-            Resolved(val) => format!("resolved = {}", val),
+        let now = || unix_now() as f64;
+        match self {
+            Suspended(val) => {
+                params.push(Value::from(*val));
+                "suspended = ?".to_string()
+            }
+            Resolved(val) => {
+                params.push(Value::from(*val));
+                "resolved = ?".to_string()
+            }
             Cardtype(val) => match val {
                 CardType::Pending => "cardtype = 0".to_string(),
                 CardType::Unfinished => "cardtype = 1".to_string(),
@@ -52,60 +72,147 @@ impl fmt::Display for CardFilter {
                 false => "cardtype != 2".to_string(),
             },
             DueUnfinished => {
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as f32;
-                format!("{} - skiptime > (skipduration * 84600)", now)
+                params.push(Value::from(now()));
+                "? - skiptime > (skipduration * 84600)".to_string()
             }
             MaxSkipDaysPassed(val) => {
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as f32;
-                let since = now - (val * 86400.);
-                format!("skiptime < {}", since)
+                params.push(Value::from(now() - (*val as f64 * 86400.)));
+                "skiptime < ?".to_string()
             }
             MinSkipDaysPassed(val) => {
-                let now = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as f32;
-                let since = now - (val * 86400.);
-                format!("skiptime > {}", since)
+                params.push(Value::from(now() - (*val as f64 * 86400.)));
+                "skiptime > ?".to_string()
             }
-            Source(val) => format!("source = {}", val),
-            MaxPosition(val) => format!("position < {}", val),
-            MinPosition(val) => format!("position > {}", val),
-            Minstability(val) => format!("stability > {}", val),
-            Maxstability(val) => format!("stability < {}", val),
-            StrengthRange(val) => format!("strength BETWEEN {} and {}", val.0, val.1),
-            Contains(val) => format!("(question LIKE '%{}%' or answer LIKE '%{}%')", val, val),
-            Topics(vec) => {
-                let mut topicstr = String::from("(");
-                for id in vec {
-                    topicstr.push_str(&format!("topic = {} OR ", id));
-                }
-                // removes the final " OR "
-                for _ in 0..4 {
-                    topicstr.pop();
+            Source(val) => {
+                params.push(Value::from(*val));
+                "source = ?".to_string()
+            }
+            MaxPosition(val) => {
+                params.push(Value::from(*val));
+                "position < ?".to_string()
+            }
+            MinPosition(val) => {
+                params.push(Value::from(*val));
+                "position > ?".to_string()
+            }
+            Minstability(val) => {
+                params.push(Value::from(*val));
+                "stability > ?".to_string()
+            }
+            Maxstability(val) => {
+                params.push(Value::from(*val));
+                "stability < ?".to_string()
+            }
+            StrengthRange(val) => {
+                params.push(Value::from(val.0 as f64));
+                params.push(Value::from(val.1 as f64));
+                "strength BETWEEN ? and ?".to_string()
+            }
+            BelowRetention(overrides, default) if overrides.is_empty() => {
+                params.push(Value::from(*default as f64));
+                "strength < ?".to_string()
+            }
+            BelowRetention(overrides, default) => {
+                let mut retention = String::from("(CASE topic ");
+                for (topic, val) in overrides {
+                    params.push(Value::from(*topic));
+                    params.push(Value::from(*val as f64));
+                    retention.push_str("WHEN ? THEN ? ");
                 }
-                topicstr.push(')');
-                topicstr
+                params.push(Value::from(*default as f64));
+                retention.push_str("ELSE ? END)");
+                format!("strength < {}", retention)
             }
-        };
-        write!(f, "{}", text)
+            Contains(val) => {
+                // the search is taken literally, so % and _ don't act as wildcards
+                let escaped = val
+                    .replace('\\', "\\\\")
+                    .replace('%', "\\%")
+                    .replace('_', "\\_");
+                params.push(Value::from(escaped.clone()));
+                params.push(Value::from(escaped));
+                r"(cards.question LIKE '%' || ? || '%' ESCAPE '\' or cards.answer LIKE '%' || ? || '%' ESCAPE '\')"
+                    .to_string()
+            }
+            Search(val) => {
+                params.push(Value::from(val.clone()));
+                "cards_fts MATCH ?".to_string()
+            }
+            CreatedAfter(val) => {
+                params.push(Value::from(*val));
+                "cards.created > ?".to_string()
+            }
+            CreatedBefore(val) => {
+                params.push(Value::from(*val));
+                "cards.created < ?".to_string()
+            }
+            ModifiedAfter(val) => {
+                params.push(Value::from(*val));
+                "cards.modified > ?".to_string()
+            }
+            ModifiedBefore(val) => {
+                params.push(Value::from(*val));
+                "cards.modified < ?".to_string()
+            }
+            Tagged(val) => {
+                params.push(Value::from(val.clone()));
+                "cards.id IN (SELECT card FROM card_tags JOIN tags ON tags.id = card_tags.tag WHERE tags.name = ?)"
+                    .to_string()
+            }
+            NotTagged(val) => {
+                params.push(Value::from(val.clone()));
+                "cards.id NOT IN (SELECT card FROM card_tags JOIN tags ON tags.id = card_tags.tag WHERE tags.name = ?)"
+                    .to_string()
+            }
+            Topics(vec) if vec.is_empty() => "0".to_string(),
+            Topics(vec) => {
+                let placeholders = vec![String::from("?"); vec.len()].join(", ");
+                params.extend(vec.iter().map(|id| Value::from(*id)));
+                format!("topic IN ({})", placeholders)
+            }
+        }
+    }
+}
+
+/// What `CardQuery` sorts its results by.
+#[derive(Clone, Copy)]
+pub enum CardOrder {
+    Id,
+    Question,
+    Strength,
+    Stability,
+    Topic,
+    Random,
+    Created,
+    Modified,
+}
+
+impl CardOrder {
+    fn column(&self) -> &'static str {
+        match self {
+            CardOrder::Id => "cards.id",
+            CardOrder::Question => "cards.question",
+            CardOrder::Strength => "strength",
+            CardOrder::Stability => "stability",
+            CardOrder::Topic => "topic",
+            CardOrder::Random => "RANDOM()",
+            CardOrder::Created => "cards.created",
+            CardOrder::Modified => "cards.modified",
+        }
     }
 }
 
 #[derive(Default)]
 pub struct CardQuery {
     filters: Vec<CardFilter>,
+    order: Option<(CardOrder, bool)>,
     limit: Option<u32>,
+    offset: Option<u32>,
 }
 
 impl CardQuery {
-    fn make_query(&self) -> String {
+    fn make_query(&self) -> (String, Vec<Value>) {
+        let mut params = vec![];
         let mut query = r#"SELECT * 
             FROM cards
             LEFT OUTER JOIN finished_cards
@@ -117,21 +224,38 @@ impl CardQuery {
 
             "#
         .to_string();
+        let searching = self
+            .filters
+            .iter()
+            .any(|filter| matches!(filter, CardFilter::Search(_)));
+        if searching {
+            query.push_str("JOIN cards_fts ON cards.id = cards_fts.rowid ");
+        }
         if !self.filters.is_empty() {
+            let clauses: Vec<String> = self
+                .filters
+                .iter()
+                .map(|filter| filter.clause(&mut params))
+                .collect();
             query.push_str("WHERE ");
-            for filter in &self.filters {
-                query.push_str(&format!("{} and ", filter));
-            }
-
-            // removes the final " and "
-            for _ in 0..4 {
-                query.pop();
+            query.push_str(&clauses.join(" and "));
+        }
+        match self.order {
+            Some((order, ascending)) => {
+                let direction = if ascending { "ASC" } else { "DESC" };
+                query.push_str(&format!(" ORDER BY {} {}", order.column(), direction));
             }
+            // best match first
+            None if searching => query.push_str(" ORDER BY cards_fts.rank"),
+            None => {}
         }
-        if let Some(limit) = self.limit {
-            query.push_str(&format!("LIMIT {}", limit));
+        if self.limit.is_some() || self.offset.is_some() {
+            // sqlite only takes an offset after a limit, -1 means no limit
+            params.push(Value::from(self.limit.map_or(-1, i64::from)));
+            params.push(Value::from(self.offset.unwrap_or(0)));
+            query.push_str(" LIMIT ? OFFSET ?");
         }
-        query
+        (query, params)
     }
 
     pub fn suspended(mut self, val: bool) -> Self {
@@ -158,6 +282,11 @@ impl CardQuery {
         self.filters.push(CardFilter::StrengthRange(val));
         self
     }
+    /// Cards whose strength has fallen below the desired retention of their topic.
+    pub fn below_retention(mut self, overrides: Vec<(TopicID, f32)>, default: f32) -> Self {
+        self.filters.push(CardFilter::BelowRetention(overrides, default));
+        self
+    }
     pub fn minimum_stability(mut self, val: u32) -> Self {
         self.filters.push(CardFilter::Minstability(val));
         self
@@ -170,6 +299,24 @@ impl CardQuery {
         self.filters.push(CardFilter::Contains(val));
         self
     }
+    /// Full-text search over question and answer, see `fts_query` for the syntax. The
+    /// results come best match first unless another order is given. Blank input matches
+    /// every card.
+    pub fn search(mut self, val: &str) -> Self {
+        if let Some(query) = fts_query(val) {
+            self.filters.push(CardFilter::Search(query));
+        }
+        self
+    }
+    /// Cards with the tag, ignoring case.
+    pub fn tagged(mut self, val: &str) -> Self {
+        self.filters.push(CardFilter::Tagged(val.to_string()));
+        self
+    }
+    pub fn not_tagged(mut self, val: &str) -> Self {
+        self.filters.push(CardFilter::NotTagged(val.to_string()));
+        self
+    }
     pub fn topics(mut self, val: Vec<TopicID>) -> Self {
         self.filters.push(CardFilter::Topics(val));
         self
@@ -202,42 +349,87 @@ impl CardQuery {
         self.filters.push(CardFilter::Cardtype(val));
         self
     }
+    /// Cards created after the given unix time.
+    pub fn created_after(mut self, val: u32) -> Self {
+        self.filters.push(CardFilter::CreatedAfter(val));
+        self
+    }
+    pub fn created_before(mut self, val: u32) -> Self {
+        self.filters.push(CardFilter::CreatedBefore(val));
+        self
+    }
+    /// Cards whose question, answer, topic or source changed after the given unix time.
+    pub fn modified_after(mut self, val: u32) -> Self {
+        self.filters.push(CardFilter::ModifiedAfter(val));
+        self
+    }
+    pub fn modified_before(mut self, val: u32) -> Self {
+        self.filters.push(CardFilter::ModifiedBefore(val));
+        self
+    }
     pub fn limit(mut self, val: u32) -> Self {
         self.limit = Some(val);
         self
     }
-    pub fn fetch_card_ids(self, conn: &Arc<Mutex<Connection>>) -> Vec<CardID> {
-        let query = self.make_query();
-        let mut cardvec = Vec::<CardID>::new();
-        conn.lock()
-            .unwrap()
-            .prepare(&query)
-            .unwrap()
-            .query_map([], |row| {
-                cardvec.push(row.get(0).unwrap());
-                Ok(())
-            })
-            .unwrap()
-            .for_each(|_| {});
-        cardvec
-    }
-    pub fn fetch_carditems(self, conn: &Arc<Mutex<Connection>>) -> Vec<CardItem> {
-        let query = self.make_query();
-        let mut cardvec = Vec::<CardItem>::new();
-        conn.lock()
-            .unwrap()
-            .prepare(&query)
-            .unwrap()
-            .query_map([], |row| {
-                cardvec.push(CardItem {
-                    question: row.get(1).unwrap(),
-                    id: row.get(0).unwrap(),
-                });
-                Ok(())
+    /// Skips the first `val` results, together with `limit` this gives pages of cards.
+    pub fn offset(mut self, val: u32) -> Self {
+        self.offset = Some(val);
+        self
+    }
+    pub fn order_by(mut self, order: CardOrder, ascending: bool) -> Self {
+        self.order = Some((order, ascending));
+        self
+    }
+    /// How many cards the query matches, without fetching them.
+    pub fn count(self, conn: &Arc<Mutex<Connection>>) -> Result<u32> {
+        let (query, params) = self.make_query();
+        conn.lock().unwrap().query_row(
+            &format!("SELECT COUNT(*) FROM ({})", query),
+            params_from_iter(params),
+            |row| row.get(0),
+        )
+    }
+    pub fn fetch_card_ids(self, conn: &Arc<Mutex<Connection>>) -> Result<Vec<CardID>> {
+        let (query, params) = self.make_query();
+        let guard = conn.lock().unwrap();
+        let mut stmt = guard.prepare(&query)?;
+        let rows = stmt.query_map(params_from_iter(params), |row| row.get(0))?;
+        rows.collect()
+    }
+    pub fn fetch_carditems(self, conn: &Arc<Mutex<Connection>>) -> Result<Vec<CardItem>> {
+        let (query, params) = self.make_query();
+        let guard = conn.lock().unwrap();
+        let mut stmt = guard.prepare(&query)?;
+        let rows = stmt.query_map(params_from_iter(params), |row| {
+            Ok(CardItem {
+                question: row.get(1)?,
+                id: row.get(0)?,
             })
-            .unwrap()
-            .for_each(|_| {});
-        cardvec
+        })?;
+        rows.collect()
+    }
+}
+
+/// Turns what the user typed into an FTS5 query. Every word matches as a prefix, text in
+/// double quotes matches as an exact phrase, and all of them have to be present. Nothing
+/// typed is interpreted as FTS5 syntax, so any input is a valid query.
+pub fn fts_query(input: &str) -> Option<String> {
+    let quote = |text: &str| format!("\"{}\"", text.replace('"', "\"\""));
+    let mut terms = vec![];
+    for (idx, part) in input.split('"').enumerate() {
+        // every other part is inside quotes, an unclosed quote runs to the end
+        if idx % 2 == 1 {
+            if !part.trim().is_empty() {
+                terms.push(quote(part.trim()));
+            }
+        } else {
+            terms.extend(part.split_whitespace().map(|word| format!("{}*", quote(word))));
+        }
+    }
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
     }
 }
 
@@ -251,65 +443,70 @@ pub fn prev_id(conn: &Arc<Mutex<Connection>>) -> Result<u32> {
     Ok(conn.lock().unwrap().last_insert_rowid() as u32)
 }
 
-pub fn fetch_question(conn: &Arc<Mutex<Connection>>, cid: CardID) -> String {
-    fetch_card(conn, cid).question
+pub fn fetch_question(conn: &Arc<Mutex<Connection>>, cid: CardID) -> Result<String> {
+    Ok(fetch_card(conn, cid)?.question)
 }
 
-pub fn get_topic_of_card(conn: &Arc<Mutex<Connection>>, cid: CardID) -> TopicID {
-    fetch_card(conn, cid).topic
+pub fn get_topic_of_card(conn: &Arc<Mutex<Connection>>, cid: CardID) -> Result<TopicID> {
+    Ok(fetch_card(conn, cid)?.topic)
 }
 
-pub fn fill_dependencies(conn: &Arc<Mutex<Connection>>, mut card: Card) -> Card {
-    card.dependents = get_dependents(conn, card.id).unwrap();
-    card.dependencies = get_dependencies(conn, card.id).unwrap();
-    card
+/// The topic of every card.
+pub fn get_card_topics(conn: &Arc<Mutex<Connection>>) -> Result<HashMap<CardID, TopicID>> {
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare("SELECT id, topic FROM cards")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
 }
 
-pub fn fetch_card(conn: &Arc<Mutex<Connection>>, cid: u32) -> Card {
+pub fn fill_dependencies(conn: &Arc<Mutex<Connection>>, mut card: Card) -> Result<Card> {
+    card.dependents = get_dependents(conn, card.id)?;
+    card.dependencies = get_dependencies(conn, card.id)?;
+    Ok(card)
+}
+
+pub fn fetch_card(conn: &Arc<Mutex<Connection>>, cid: u32) -> Result<Card> {
     let card = conn
         .lock()
         .unwrap()
-        .query_row("SELECT * FROM cards WHERE id=?", [cid], |row| row2card(row))
-        .expect(&format!("Failed to query following card: {}", cid));
-    fill_dependencies(conn, card)
+        .query_row("SELECT * FROM cards WHERE id=?", [cid], row2card)?;
+    let mut card = fill_dependencies(conn, card)?;
+    card.tags = get_tags(conn, cid)?;
+    Ok(card)
 }
 
 pub fn get_topics(conn: &Arc<Mutex<Connection>>) -> Result<Vec<Topic>> {
-    let mut vecoftops = Vec::<Topic>::new();
-    conn.lock()
-        .unwrap()
-        .prepare("SELECT * FROM topics")?
-        .query_map([], |row| {
-            Ok(Topic {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                parent: row.get(2)?,
-                children: Vec::<CardID>::new(),
-                ancestors: 0,
-                relpos: row.get(3)?,
-            })
-        })?
-        .for_each(|topic| {
-            vecoftops.push(topic.unwrap());
-        });
-    Ok(vecoftops)
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare("SELECT * FROM topics")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Topic {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            parent: row.get(2)?,
+            children: Vec::<CardID>::new(),
+            ancestors: 0,
+            relpos: row.get(3)?,
+        })
+    })?;
+    rows.collect()
 }
 
+/// The review history of a card, oldest review first.
 pub fn get_history(conn: &Arc<Mutex<Connection>>, id: u32) -> Result<Vec<Review>> {
-    let mut vecofrows = Vec::<Review>::new();
-    conn.lock()
-        .unwrap()
-        .prepare("SELECT * FROM revlog WHERE cid = ?")?
-        .query_map([id], |row| {
-            vecofrows.push(Review {
-                grade: RecallGrade::from(row.get(2)?).unwrap(),
-                date: row.get(0)?,
-                answertime: row.get(3)?,
-            });
-            Ok(())
-        })?
-        .for_each(|_| {});
-    Ok(vecofrows)
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare(
+        "SELECT unix, grade, qtime, atime FROM revlog WHERE cid = ? ORDER BY unix, rowid",
+    )?;
+    let rows = stmt.query_map([id], |row| {
+        Ok(Review {
+            grade: RecallGrade::from(row.get(1)?)
+                .ok_or(rusqlite::Error::IntegralValueOutOfRange(1, row.get(1)?))?,
+            date: row.get(0)?,
+            questiontime: row.get(2)?,
+            answertime: row.get(3)?,
+        })
+    })?;
+    rows.collect()
 }
 
 // pub fn row2card(conn: &Arc<Mutex<Connection>>, row: &Row) -> Result<Card>{
@@ -319,7 +516,7 @@ pub fn row2card(row: &Row) -> Result<Card> {
         0 => CardType::Pending,
         1 => CardType::Unfinished,
         2 => CardType::Finished,
-        _ => panic!(),
+        other => return Err(rusqlite::Error::IntegralValueOutOfRange(7, other.into())),
     };
     let id = row.get(0)?;
 
@@ -350,70 +547,40 @@ pub fn row2card(row: &Row) -> Result<Card> {
         dependencies: Vec::new(),
         topic: row.get(10)?,
         source: row.get(11)?,
+        created: row.get(12)?,
+        modified: row.get(13)?,
+        tags: vec![],
     })
 }
 
 pub fn load_cards(conn: &Arc<Mutex<Connection>>) -> Result<Vec<Card>> {
-    let mut cardvec = Vec::<Card>::new();
-    conn.lock()
+    let mut cardvec = conn
+        .lock()
         .unwrap()
         .prepare("SELECT * FROM cards")?
-        .query_map([], |row| {
-            cardvec.push(row2card(row).unwrap());
-            Ok(())
-        })?
-        .for_each(|_| {});
+        .query_map([], row2card)?
+        .collect::<Result<Vec<Card>>>()?;
     for i in 0..cardvec.len() {
         let id = cardvec[i].id;
-        cardvec[i].dependencies = get_dependencies(conn, id).unwrap();
-        cardvec[i].dependents = get_dependents(conn, id).unwrap();
+        cardvec[i].dependencies = get_dependencies(conn, id)?;
+        cardvec[i].dependents = get_dependents(conn, id)?;
     }
 
     Ok(cardvec)
 }
 
-pub fn load_card_matches(conn: &Arc<Mutex<Connection>>, search: &str) -> Result<Vec<Card>> {
-    let mut cardvec = Vec::<Card>::new();
-    conn
-        .lock()
-        .unwrap()
-        .prepare("SELECT * FROM cards WHERE (question LIKE '%' || ?1 || '%') OR (answer LIKE '%' || ?1 || '%') LIMIT 50")?
-        .query_map([search], |row| {
-            cardvec.push(row2card(row).unwrap());
-            Ok(())
-        })?.for_each(|_|{});
-    for i in 0..cardvec.len() {
-        let id = cardvec[i].id;
-        cardvec[i].dependencies = get_dependencies(conn, id).unwrap();
-        cardvec[i].dependents = get_dependents(conn, id).unwrap();
-    }
-    Ok(cardvec)
-}
-
 pub fn get_dependents(conn: &Arc<Mutex<Connection>>, dependency: u32) -> Result<Vec<u32>> {
-    let mut depvec = Vec::<CardID>::new();
-    conn.lock()
-        .unwrap()
-        .prepare("SELECT dependent FROM dependencies where dependency = ?")?
-        .query_map([dependency], |row| {
-            depvec.push(row.get(0).unwrap());
-            Ok(())
-        })?
-        .for_each(|_| {});
-    Ok(depvec)
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare("SELECT dependent FROM dependencies where dependency = ?")?;
+    let rows = stmt.query_map([dependency], |row| row.get(0))?;
+    rows.collect()
 }
 
 pub fn get_dependencies(conn: &Arc<Mutex<Connection>>, dependent: CardID) -> Result<Vec<CardID>> {
-    let mut depvec = Vec::<CardID>::new();
-    conn.lock()
-        .unwrap()
-        .prepare("SELECT dependency FROM dependencies where dependent = ?")?
-        .query_map([dependent], |row| {
-            depvec.push(row.get(0).unwrap());
-            Ok(())
-        })?
-        .for_each(|_| {});
-    Ok(depvec)
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare("SELECT dependency FROM dependencies where dependent = ?")?;
+    let rows = stmt.query_map([dependent], |row| row.get(0))?;
+    rows.collect()
 }
 
 use crate::utils::card::CardType;
@@ -429,21 +596,21 @@ struct IncTemp {
     isactive: bool,
 }
 
-pub fn fetch_media(conn: &Arc<Mutex<Connection>>, id: CardID) -> MediaContents {
-    let card = fetch_card(conn, id);
-    MediaContents {
+pub fn fetch_media(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<MediaContents> {
+    let card = fetch_card(conn, id)?;
+    Ok(MediaContents {
         frontaudio: card.frontaudio,
         backaudio: card.backaudio,
         frontimage: card.frontimage,
         backimage: card.backimage,
-    }
+    })
 }
 
 // -------------------------------------------------------------- //
 
 pub fn get_incread(conn: &Arc<Mutex<Connection>>, id: u32) -> Result<IncRead> {
-    let extracts = load_extracts(conn, id).unwrap();
-    let cloze_cards = CardQuery::default().source(id).fetch_carditems(conn);
+    let extracts = load_extracts(conn, id)?;
+    let cloze_cards = CardQuery::default().source(id).fetch_carditems(conn)?;
     conn.lock()
         .unwrap()
         .query_row("SELECT * FROM incread WHERE id = ?", [id], |row| {
@@ -451,11 +618,7 @@ pub fn get_incread(conn: &Arc<Mutex<Connection>>, id: u32) -> Result<IncRead> {
                 id,
                 parent: row.get(1)?,
                 topic: row.get(2)?,
-                source: Field::new_with_text(
-                    row.get(3).unwrap(),
-                    row.get(7).unwrap(),
-                    row.get(8).unwrap(),
-                ),
+                source: Field::new_with_text(row.get(3)?, row.get(7)?, row.get(8)?),
                 extracts: StatefulList::with_items(extracts),
                 clozes: StatefulList::with_items(cloze_cards),
                 isactive: row.get(4)?,
@@ -466,66 +629,70 @@ pub fn get_incread(conn: &Arc<Mutex<Connection>>, id: u32) -> Result<IncRead> {
 use crate::utils::incread::IncListItem;
 
 pub fn load_inc_items(conn: &Arc<Mutex<Connection>>, topic: TopicID) -> Result<Vec<IncListItem>> {
-    let mut incvec = Vec::<IncListItem>::new();
-    conn.lock()
-        .unwrap()
-        .prepare("SELECT * FROM incread where parent = 0 and topic = ?")
-        .unwrap()
-        .query_map([topic], |row| {
-            incvec.push(IncListItem {
-                text: row.get(3)?,
-                id: row.get(0)?,
-            });
-            Ok(())
-        })?
-        .for_each(|_| {});
-    Ok(incvec)
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare("SELECT * FROM incread where parent = 0 and topic = ?")?;
+    let rows = stmt.query_map([topic], |row| {
+        Ok(IncListItem {
+            text: row.get(3)?,
+            id: row.get(0)?,
+        })
+    })?;
+    rows.collect()
 }
 
 pub fn load_extracts(conn: &Arc<Mutex<Connection>>, parent: IncID) -> Result<Vec<IncListItem>> {
-    let mut incvec = Vec::<IncListItem>::new();
-    conn.lock()
-        .unwrap()
-        .prepare("SELECT * FROM incread where parent = ?")
-        .unwrap()
-        .query_map([parent], |row| {
-            incvec.push(IncListItem {
-                text: row.get(3)?,
-                id: row.get(0)?,
-            });
-            Ok(())
-        })?
-        .for_each(|_| {});
-    Ok(incvec)
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare("SELECT * FROM incread where parent = ?")?;
+    let rows = stmt.query_map([parent], |row| {
+        Ok(IncListItem {
+            text: row.get(3)?,
+            id: row.get(0)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Incremental reading texts matching a full-text search, best match first. Each comes
+/// with an excerpt around the match instead of the whole text.
+pub fn search_incread(
+    conn: &Arc<Mutex<Connection>>,
+    search: &str,
+    limit: u32,
+) -> Result<Vec<IncListItem>> {
+    let query = match fts_query(search) {
+        Some(query) => query,
+        None => return Ok(vec![]),
+    };
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare(
+        "SELECT rowid, snippet(incread_fts, 0, '', '', '...', 12) FROM incread_fts
+        WHERE incread_fts MATCH ? ORDER BY rank LIMIT ?",
+    )?;
+    let rows = stmt.query_map(params![query, limit], |row| {
+        Ok(IncListItem {
+            text: row.get::<usize, String>(1)?.replace('\n', " "),
+            id: row.get(0)?,
+        })
+    })?;
+    rows.collect()
 }
 
 pub fn load_active_inc(conn: &Arc<Mutex<Connection>>) -> Result<Vec<IncID>> {
-    let current_time = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as u32;
-    let mut incvec = Vec::<IncID>::new();
-    conn.lock()
-        .unwrap()
-        .prepare(
-            "SELECT id FROM incread where active = 1 and ((? - skiptime) > (skipduration * 86400))",
-        )
-        .unwrap()
-        .query_map([current_time], |row| {
-            incvec.push(row.get(0).unwrap());
-            Ok(())
-        })?
-        .for_each(|_| {});
-    Ok(incvec)
+    let current_time = unix_now();
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare(
+        "SELECT id FROM incread where active = 1 and ((? - skiptime) > (skipduration * 86400))",
+    )?;
+    let rows = stmt.query_map([current_time], |row| row.get(0))?;
+    rows.collect()
 }
 
 use crate::widgets::cardlist::CardItem;
 pub fn load_inc_text(conn: &Arc<Mutex<Connection>>, id: IncID) -> Result<String> {
     conn.lock()
         .unwrap()
-        .prepare("SELECT source FROM incread where id = ?")
-        .unwrap()
-        .query_row([id], |row| Ok(row.get(0).unwrap()))
+        .prepare("SELECT source FROM incread where id = ?")?
+        .query_row([id], |row| row.get(0))
 }
 
 pub fn load_inc_title(
@@ -533,7 +700,7 @@ pub fn load_inc_title(
     incid: IncID,
     titlelen: u16,
 ) -> Result<String> {
-    let mut source = load_inc_text(conn, incid).unwrap();
+    let mut source = load_inc_text(conn, incid)?;
     source.truncate(titlelen.into());
     if source.len() < 5 {
         source = "Empty Source".to_string();
@@ -544,9 +711,8 @@ pub fn load_inc_title(
 pub fn get_topic_of_inc(conn: &Arc<Mutex<Connection>>, id: IncID) -> Result<TopicID> {
     conn.lock()
         .unwrap()
-        .prepare("SELECT topic FROM incread where id = ?")
-        .unwrap()
-        .query_row([id], |row| Ok(row.get(0).unwrap()))
+        .prepare("SELECT topic FROM incread where id = ?")?
+        .query_row([id], |row| row.get(0))
 }
 
 pub fn get_skipduration(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<u32> {
@@ -573,18 +739,12 @@ pub fn get_skiptime(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<u32> {
     )
 }
 
-pub fn get_stability(conn: &Arc<Mutex<Connection>>, id: CardID) -> f32 {
-    conn.lock()
-        .unwrap()
-        .query_row(
-            "select stability FROM finished_cards WHERE id=?",
-            [id],
-            |row| row.get(0),
-        )
-        .expect(&format!(
-            "Couldn't find stability of card with following id: {}",
-            id
-        ))
+pub fn get_stability(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<f32> {
+    conn.lock().unwrap().query_row(
+        "select stability FROM finished_cards WHERE id=?",
+        [id],
+        |row| row.get(0),
+    )
 }
 
 pub fn get_strength(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<f32> {
@@ -595,28 +755,204 @@ pub fn get_strength(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<f32> {
     )
 }
 
-pub fn is_resolved(conn: &Arc<Mutex<Connection>>, id: CardID) -> bool {
+pub fn is_resolved(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<bool> {
     conn.lock()
         .unwrap()
         .query_row("select resolved FROM cards WHERE id=?", [id], |row| {
             row.get(0)
         })
-        .unwrap()
 }
 
 //use crate::utils::card::CardType;
-pub fn get_cardtype(conn: &Arc<Mutex<Connection>>, id: CardID) -> CardType {
+pub fn get_cardtype(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<CardType> {
     match conn
         .lock()
         .unwrap()
         .query_row("select cardtype FROM cards WHERE id=?", [id], |row| {
-            row.get::<usize, usize>(0)
+            row.get::<usize, u32>(0)
+        })? {
+        0 => Ok(CardType::Pending),
+        1 => Ok(CardType::Unfinished),
+        2 => Ok(CardType::Finished),
+        other => Err(rusqlite::Error::IntegralValueOutOfRange(0, other.into())),
+    }
+}
+
+/// The review history of every card that has one, oldest review first.
+pub fn get_all_histories(conn: &Arc<Mutex<Connection>>) -> Result<Vec<(CardID, Vec<Review>)>> {
+    let mut histories = Vec::<(CardID, Vec<Review>)>::new();
+    let mut lastcid = None;
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard
+        .prepare("SELECT unix, cid, grade, qtime, atime FROM revlog ORDER BY cid, unix, rowid")?;
+    let rows = stmt.query_map([], |row| {
+        let cid: CardID = row.get(1)?;
+        let review = Review {
+            grade: RecallGrade::from(row.get(2)?)
+                .ok_or(rusqlite::Error::IntegralValueOutOfRange(2, row.get(2)?))?,
+            date: row.get(0)?,
+            questiontime: row.get(3)?,
+            answertime: row.get(4)?,
+        };
+        Ok((cid, review))
+    })?;
+    for row in rows {
+        let (cid, review) = row?;
+        if lastcid != Some(cid) {
+            histories.push((cid, vec![]));
+            lastcid = Some(cid);
+        }
+        histories.last_mut().unwrap().1.push(review);
+    }
+    Ok(histories)
+}
+
+/// Cards that were reviewed for the first time after `since`, counted per topic.
+pub fn new_cards_since(conn: &Arc<Mutex<Connection>>, since: u32) -> Result<HashMap<TopicID, u32>> {
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare(
+        "SELECT cards.topic, COUNT(*) FROM cards 
+        JOIN (SELECT cid, MIN(unix) AS first FROM revlog GROUP BY cid) AS firsts
+        ON cards.id = firsts.cid
+        WHERE firsts.first >= ?
+        GROUP BY cards.topic",
+    )?;
+    let rows = stmt.query_map([since], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+/// Reviews done after `since`, not counting the first review of a card, per topic.
+pub fn reviews_since(conn: &Arc<Mutex<Connection>>, since: u32) -> Result<HashMap<TopicID, u32>> {
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare(
+        "SELECT cards.topic, COUNT(*) FROM revlog 
+        JOIN cards ON cards.id = revlog.cid
+        WHERE revlog.unix >= ?
+        AND revlog.unix > (SELECT MIN(first.unix) FROM revlog AS first WHERE first.cid = revlog.cid)
+        GROUP BY cards.topic",
+    )?;
+    let rows = stmt.query_map([since], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+pub fn is_leech(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<bool> {
+    conn.lock()
+        .unwrap()
+        .query_row("SELECT EXISTS(SELECT 1 FROM leeches WHERE id = ?)", [id], |row| row.get(0))
+}
+
+/// Card ids and lapse counts of all leeches, worst first.
+pub fn load_leeches(conn: &Arc<Mutex<Connection>>) -> Result<Vec<(CardID, u32)>> {
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare("SELECT id, lapses FROM leeches ORDER BY lapses DESC")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+pub fn get_anki_note(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<Option<NoteID>> {
+    conn.lock()
+        .unwrap()
+        .query_row("SELECT note FROM anki_notes WHERE cid = ?", [id], |row| {
+            row.get::<usize, i64>(0)
         })
+        .optional()
+        .map(|note| note.map(|note| note as NoteID))
+}
+
+pub fn cards_reviewed_since(conn: &Arc<Mutex<Connection>>, since: u32) -> Result<Vec<CardID>> {
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare("SELECT DISTINCT cid FROM revlog WHERE unix >= ?")?;
+    let rows = stmt.query_map([since], |row| row.get(0))?;
+    rows.collect()
+}
+
+pub fn get_card_state(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<CardState> {
+    let card = fetch_card(conn, id)?;
+    let conn = conn.lock().unwrap();
+    let finished = conn
+        .query_row(
+            "SELECT strength, stability FROM finished_cards WHERE id = ?",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let unfinished = conn
+        .query_row(
+            "SELECT skiptime, skipduration FROM unfinished_cards WHERE id = ?",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    let pending = conn
+        .query_row("SELECT position FROM pending_cards WHERE id = ?", [id], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    let leech = conn
+        .query_row("SELECT lapses, flagged FROM leeches WHERE id = ?", [id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()?;
+    Ok(CardState {
+        cardtype: card.cardtype,
+        suspended: card.suspended,
+        finished,
+        unfinished,
+        pending,
+        leech,
+    })
+}
+
+/// Strength and stability of every finished card.
+pub fn get_memory_states(conn: &Arc<Mutex<Connection>>) -> Result<Vec<(CardID, f32, f32)>> {
+    let mut states = vec![];
+    conn.lock()
         .unwrap()
-    {
-        0 => CardType::Pending,
-        1 => CardType::Unfinished,
-        2 => CardType::Finished,
-        _ => panic!(),
+        .prepare("SELECT id, strength, stability FROM finished_cards")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .try_for_each(|state| -> Result<()> {
+            states.push(state?);
+            Ok(())
+        })?;
+    Ok(states)
+}
+
+/// Which of the finished, unfinished and pending tables every card has a row in, once for
+/// every row.
+pub fn get_state_rows(conn: &Arc<Mutex<Connection>>) -> Result<HashMap<CardID, Vec<CardType>>> {
+    let mut staterows: HashMap<CardID, Vec<CardType>> = HashMap::new();
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare(
+        "SELECT id, 2 FROM finished_cards
+        UNION ALL SELECT id, 1 FROM unfinished_cards
+        UNION ALL SELECT id, 0 FROM pending_cards",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let cardtype = match row.get::<usize, u32>(1)? {
+            0 => CardType::Pending,
+            1 => CardType::Unfinished,
+            _ => CardType::Finished,
+        };
+        Ok((row.get(0)?, cardtype))
+    })?;
+    for row in rows {
+        let (id, cardtype) = row?;
+        staterows.entry(id).or_default().push(cardtype);
     }
+    Ok(staterows)
+}
+
+pub fn get_incread_ids(conn: &Arc<Mutex<Connection>>) -> Result<Vec<IncID>> {
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare("SELECT id FROM incread")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Table and rowid of every row whose foreign key points at nothing.
+pub fn foreign_key_violations(conn: &Arc<Mutex<Connection>>) -> Result<Vec<(String, i64)>> {
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare("PRAGMA foreign_key_check")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
 }
@@ -1,35 +1,30 @@
 use crate::utils::aliases::*;
-use crate::utils::card::{Card, CardType, Review}; //, Status, Topic, Review}
+use crate::utils::card::{Card, CardType, RecallGrade, Review}; //, Status, Topic, Review}
+use crate::utils::misc::unix_now;
+use crate::utils::sql::tags::insert_tags;
 use crate::utils::sql::update::set_cardtype;
 use rusqlite::{params, Connection, Result};
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
-
-pub fn save_card(conn: &Arc<Mutex<Connection>>, card: Card) -> CardID {
-    //let time_added = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as u32;
-    let frontaudio: Option<String> = card
-        .frontaudio
-        .map(|x| x.into_os_string().into_string().unwrap());
-    let backaudio: Option<String> = card
-        .backaudio
-        .map(|x| x.into_os_string().into_string().unwrap());
-    let frontimage: Option<String> = card
-        .frontimage
-        .map(|x| x.into_os_string().into_string().unwrap());
-    let backimage: Option<String> = card
-        .backimage
-        .map(|x| x.into_os_string().into_string().unwrap());
 
+pub fn save_card(conn: &Arc<Mutex<Connection>>, card: Card) -> Result<CardID> {
+    insert_card(&conn.lock().unwrap(), &card)
+}
+
+/// Inserts the card with its tags and the state row for its type. Takes a bare connection
+/// so a batch of cards can share one transaction.
+pub fn insert_card(conn: &Connection, card: &Card) -> Result<CardID> {
+    let now = unix_now();
+    let path = |path: &Option<std::path::PathBuf>| {
+        path.as_ref().map(|x| x.to_string_lossy().into_owned())
+    };
     let cardtype = match card.cardtype {
         CardType::Pending => 0,
         CardType::Unfinished => 1,
         CardType::Finished => 2,
     } as u32;
 
-    conn.lock()
-        .unwrap()
-        .execute(
-            "INSERT INTO cards (
+    conn.prepare_cached(
+        "INSERT INTO cards (
             question, 
             answer, 
             frontaudio, 
@@ -40,62 +35,70 @@ pub fn save_card(conn: &Arc<Mutex<Connection>>, card: Card) -> CardID {
             suspended, 
             resolved, 
             topic, 
-            source
+            source,
+            created,
+            modified
             ) 
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
-            params![
-                card.question,
-                card.answer,
-                frontaudio,
-                backaudio,
-                frontimage,
-                backimage,
-                cardtype,
-                card.suspended,
-                card.resolved,
-                card.topic,
-                card.source,
-            ],
-        )
-        .unwrap();
-
-    let id = conn.lock().unwrap().last_insert_rowid() as u32;
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?12)",
+    )?
+    .execute(params![
+        card.question,
+        card.answer,
+        path(&card.frontaudio),
+        path(&card.backaudio),
+        path(&card.frontimage),
+        path(&card.backimage),
+        cardtype,
+        card.suspended,
+        card.resolved,
+        card.topic,
+        card.source,
+        now,
+    ])?;
+
+    let id = conn.last_insert_rowid() as u32;
+    insert_tags(conn, id, &card.tags)?;
 
     match card.cardtype {
-        CardType::Pending => new_pending(conn, id).unwrap(),
-        CardType::Unfinished => new_unfinished(conn, id).unwrap(),
-        CardType::Finished => new_finished(conn, id).unwrap(),
+        CardType::Pending => insert_pending(conn, id)?,
+        CardType::Unfinished => insert_unfinished(conn, id)?,
+        CardType::Finished => insert_finished(conn, id)?,
     };
 
-    id
+    Ok(id)
 }
 
 pub fn update_both(conn: &Arc<Mutex<Connection>>, dependent: u32, dependency: u32) -> Result<()> {
     conn.lock().unwrap().execute(
-        "INSERT INTO dependencies (dependent, dependency) VALUES (?1, ?2)",
+        "INSERT OR IGNORE INTO dependencies (dependent, dependency) VALUES (?1, ?2)",
         params![dependent, dependency],
     )?;
     Ok(())
 }
 
-pub fn revlog_new(conn: &Arc<Mutex<Connection>>, card_id: u32, review: Review) -> Result<()> {
-    conn.lock().unwrap().execute(
+pub fn revlog_new(conn: &Arc<Mutex<Connection>>, card_id: u32, review: Review) -> Result<RevlogID> {
+    insert_review(&conn.lock().unwrap(), card_id, review)
+}
+
+pub fn insert_review(conn: &Connection, card_id: u32, review: Review) -> Result<RevlogID> {
+    conn.prepare_cached(
         "INSERT INTO revlog (unix, cid, grade, qtime, atime) VALUES (?1, ?2, ?3, ?4, ?5)",
-        params![
-            review.date,
-            card_id,
-            review.grade as u32,
-            review.answertime,
-            -1
-        ],
-    )?;
-    Ok(())
+    )?
+    .execute(params![
+        review.date,
+        card_id,
+        review.grade as u32,
+        review.questiontime,
+        review.answertime
+    ])?;
+    Ok(conn.last_insert_rowid())
 }
 
 pub fn new_topic(conn: &Arc<Mutex<Connection>>, name: String, parent: u32, pos: u32) -> Result<()> {
+    let now = unix_now();
     conn.lock().unwrap().execute(
-        "INSERT INTO topics (name, parent, relpos) VALUES (?1, ?2, ?3)",
-        params![name, parent, pos],
+        "INSERT INTO topics (name, parent, relpos, created, modified) VALUES (?1, ?2, ?3, ?4, ?4)",
+        params![name, parent, pos, now],
     )?;
     Ok(())
 }
@@ -107,41 +110,86 @@ pub fn new_incread(
     source: String,
     isactive: bool,
 ) -> Result<()> {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as u32;
+    let now = unix_now();
     conn.lock().unwrap().execute(
-        "INSERT INTO incread (parent, topic, source, active, skiptime, skipduration, row, column) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        "INSERT INTO incread (parent, topic, source, active, skiptime, skipduration, row, column, created, modified) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?5, ?5)",
         params![parent, topic, source, isactive, now, 1.0, 0, 0],
     )?;
     Ok(())
 }
 
 pub fn new_finished(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<()> {
-    conn.lock().unwrap().execute(
-        "INSERT INTO finished_cards (id, strength, stability) VALUES (?1, ?2, ?3)",
-        params![id, 1.0f32, 1.0f32],
-    )?;
-    set_cardtype(conn, id, CardType::Finished).unwrap();
+    insert_finished(&conn.lock().unwrap(), id)?;
+    set_cardtype(conn, id, CardType::Finished)?;
     Ok(())
 }
 
 pub fn new_unfinished(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<()> {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as u32;
-    conn.lock().unwrap().execute(
+    insert_unfinished(&conn.lock().unwrap(), id)
+}
+
+pub fn new_pending(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<()> {
+    insert_pending(&conn.lock().unwrap(), id)
+}
+
+/// The state rows of the card types, without touching the type itself.
+pub fn insert_finished(conn: &Connection, id: CardID) -> Result<()> {
+    conn.prepare_cached("INSERT INTO finished_cards (id, strength, stability) VALUES (?1, ?2, ?3)")?
+        .execute(params![id, 1.0f32, 1.0f32])?;
+    Ok(())
+}
+
+pub fn insert_unfinished(conn: &Connection, id: CardID) -> Result<()> {
+    let now = unix_now();
+    conn.prepare_cached(
         "INSERT INTO unfinished_cards (id, skiptime, skipduration) VALUES (?1, ?2, ?3)",
-        params![id, now, 1],
-    )?;
+    )?
+    .execute(params![id, now, 1])?;
     Ok(())
 }
-pub fn new_pending(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<()> {
+
+pub fn insert_pending(conn: &Connection, id: CardID) -> Result<()> {
+    conn.prepare_cached("INSERT INTO pending_cards (id, position) VALUES (?1, ?2)")?
+        .execute(params![id, 1])?;
+    Ok(())
+}
+
+pub fn new_leech(conn: &Arc<Mutex<Connection>>, id: CardID, lapses: u32) -> Result<()> {
+    let now = unix_now();
     conn.lock().unwrap().execute(
-        "INSERT INTO pending_cards (id, position) VALUES (?1, ?2)",
-        params![id, 1],
+        "INSERT OR REPLACE INTO leeches (id, lapses, flagged) VALUES (?1, ?2, ?3)",
+        params![id, lapses, now],
     )?;
     Ok(())
 }
+
+/// Saves a batch of new cards, along with the anki note each one came from, all in one
+/// transaction. The connection stays locked until the whole batch is in, so anything slow
+/// about making the cards should be done before. `keep_going` is called before every card with
+/// how many are done so far, returning false rolls everything back, as does any error. Returns
+/// whether the cards got saved. The cards can't have dependencies, so they all start out
+/// resolved.
+pub fn save_cards<I, F>(conn: &Arc<Mutex<Connection>>, cards: I, mut keep_going: F) -> Result<bool>
+where
+    I: IntoIterator<Item = (Card, Option<NoteID>)>,
+    F: FnMut(usize) -> bool,
+{
+    let mut guard = conn.lock().unwrap();
+    let tx = guard.transaction()?;
+    for (idx, (mut card, note)) in cards.into_iter().enumerate() {
+        if !keep_going(idx) {
+            return Ok(false);
+        }
+        card.resolved = true;
+        let id = insert_card(&tx, &card)?;
+        if card.cardtype == CardType::Finished {
+            insert_review(&tx, id, Review::from(&RecallGrade::Decent))?;
+        }
+        if let Some(note) = note {
+            tx.prepare_cached("INSERT OR REPLACE INTO anki_notes (cid, note) VALUES (?1, ?2)")?
+                .execute(params![id, note as i64])?;
+        }
+    }
+    tx.commit()?;
+    Ok(true)
+}
@@ -0,0 +1,386 @@
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use rusqlite::Connection;
+
+use crate::utils::backup::snapshot;
+
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every schema change, oldest first. The database's `user_version` is the version of the
+/// last one applied, so only ever append to this list, never edit an entry that shipped.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        sql: "
+        create table if not exists cards (
+            id           integer primary key,
+            question     text not null,
+            answer       text not null,
+            frontaudio   text,
+            backaudio    text,
+            frontimg     text,
+            backimg      text,
+            cardtype     integer not null,
+            suspended    bool not null,
+            resolved     bool not null,
+            topic        integer not null,
+            source       integer not null
+        );
+        create table if not exists finished_cards (
+            id           integer not null,
+            strength     real not null,
+            stability    real not null
+        );
+        create table if not exists unfinished_cards (
+            id           integer not null,
+            skiptime     integer not null,
+            skipduration integer not null
+        );
+        create table if not exists pending_cards (
+            id           integer not null,
+            position     integer not null
+        );
+        create table if not exists topics (
+            id     integer primary key,
+            name   text,
+            parent integer not null,
+            relpos integer not null
+        );
+        create table if not exists revlog (
+            unix   integer not null,
+            cid    integer not null,
+            grade  integernot null,
+            qtime  real not null,
+            atime  real not null
+        );
+        create table if not exists dependencies (
+            dependent integer not null,
+            dependency integer not null
+        );
+        create table if not exists incread (
+            id integer primary key,
+            parent integer not null,
+            topic integer not null,
+            source text not null,
+            active integer not null,
+            skiptime integer,
+            skipduration integer,
+            row integer,
+            column integer
+        );
+        create table if not exists leeches (
+            id      integer primary key,
+            lapses  integer not null,
+            flagged integer not null
+        );
+        create table if not exists anki_notes (
+            cid   integer primary key,
+            note  integer not null
+        );
+        ",
+    },
+    Migration {
+        version: 2,
+        description: "fix the type of revlog.grade",
+        // rowids are kept, they're how a review gets undone
+        sql: "
+        create table revlog_new (
+            unix   integer not null,
+            cid    integer not null,
+            grade  integer not null,
+            qtime  real not null,
+            atime  real not null
+        );
+        insert into revlog_new (rowid, unix, cid, grade, qtime, atime)
+            select rowid, unix, cid, grade, qtime, atime from revlog;
+        drop table revlog;
+        alter table revlog_new rename to revlog;
+        ",
+    },
+    Migration {
+        version: 3,
+        description: "foreign keys on everything that points at a card",
+        sql: "
+        delete from finished_cards where id not in (select id from cards);
+        delete from unfinished_cards where id not in (select id from cards);
+        delete from pending_cards where id not in (select id from cards);
+        delete from revlog where cid not in (select id from cards);
+        delete from leeches where id not in (select id from cards);
+        delete from anki_notes where cid not in (select id from cards);
+        delete from dependencies where dependent not in (select id from cards)
+            or dependency not in (select id from cards);
+
+        create table finished_cards_new (
+            id           integer not null references cards (id) on delete cascade,
+            strength     real not null,
+            stability    real not null
+        );
+        insert into finished_cards_new select * from finished_cards;
+        drop table finished_cards;
+        alter table finished_cards_new rename to finished_cards;
+
+        create table unfinished_cards_new (
+            id           integer not null references cards (id) on delete cascade,
+            skiptime     integer not null,
+            skipduration integer not null
+        );
+        insert into unfinished_cards_new select * from unfinished_cards;
+        drop table unfinished_cards;
+        alter table unfinished_cards_new rename to unfinished_cards;
+
+        create table pending_cards_new (
+            id           integer not null references cards (id) on delete cascade,
+            position     integer not null
+        );
+        insert into pending_cards_new select * from pending_cards;
+        drop table pending_cards;
+        alter table pending_cards_new rename to pending_cards;
+
+        create table revlog_new (
+            unix   integer not null,
+            cid    integer not null references cards (id) on delete cascade,
+            grade  integer not null,
+            qtime  real not null,
+            atime  real not null
+        );
+        insert into revlog_new (rowid, unix, cid, grade, qtime, atime)
+            select rowid, unix, cid, grade, qtime, atime from revlog;
+        drop table revlog;
+        alter table revlog_new rename to revlog;
+
+        create table dependencies_new (
+            dependent  integer not null references cards (id) on delete cascade,
+            dependency integer not null references cards (id) on delete cascade
+        );
+        insert into dependencies_new select * from dependencies;
+        drop table dependencies;
+        alter table dependencies_new rename to dependencies;
+
+        create table leeches_new (
+            id      integer primary key references cards (id) on delete cascade,
+            lapses  integer not null,
+            flagged integer not null
+        );
+        insert into leeches_new select * from leeches;
+        drop table leeches;
+        alter table leeches_new rename to leeches;
+
+        create table anki_notes_new (
+            cid   integer primary key references cards (id) on delete cascade,
+            note  integer not null
+        );
+        insert into anki_notes_new select * from anki_notes;
+        drop table anki_notes;
+        alter table anki_notes_new rename to anki_notes;
+
+        -- the cards and texts of a deleted topic move up to its parent
+        create trigger topic_deleted after delete on topics
+        begin
+            update cards set topic = old.parent where topic = old.id;
+            update incread set topic = old.parent where topic = old.id;
+        end;
+        ",
+    },
+    Migration {
+        version: 4,
+        description: "full-text search over cards and incremental reading texts",
+        // external content tables, the text itself stays in cards and incread
+        sql: "
+        create virtual table cards_fts using fts5 (
+            question,
+            answer,
+            content = 'cards',
+            content_rowid = 'id',
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+        insert into cards_fts (cards_fts) values ('rebuild');
+
+        create trigger cards_fts_insert after insert on cards
+        begin
+            insert into cards_fts (rowid, question, answer)
+                values (new.id, new.question, new.answer);
+        end;
+        create trigger cards_fts_delete after delete on cards
+        begin
+            insert into cards_fts (cards_fts, rowid, question, answer)
+                values ('delete', old.id, old.question, old.answer);
+        end;
+        create trigger cards_fts_update after update of question, answer on cards
+        begin
+            insert into cards_fts (cards_fts, rowid, question, answer)
+                values ('delete', old.id, old.question, old.answer);
+            insert into cards_fts (rowid, question, answer)
+                values (new.id, new.question, new.answer);
+        end;
+
+        create virtual table incread_fts using fts5 (
+            source,
+            content = 'incread',
+            content_rowid = 'id',
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+        insert into incread_fts (incread_fts) values ('rebuild');
+
+        create trigger incread_fts_insert after insert on incread
+        begin
+            insert into incread_fts (rowid, source) values (new.id, new.source);
+        end;
+        create trigger incread_fts_delete after delete on incread
+        begin
+            insert into incread_fts (incread_fts, rowid, source)
+                values ('delete', old.id, old.source);
+        end;
+        create trigger incread_fts_update after update of source on incread
+        begin
+            insert into incread_fts (incread_fts, rowid, source)
+                values ('delete', old.id, old.source);
+            insert into incread_fts (rowid, source) values (new.id, new.source);
+        end;
+        ",
+    },
+    Migration {
+        version: 5,
+        description: "creation and modification times on cards, topics and texts",
+        sql: "
+        alter table cards add column created integer not null default 0;
+        alter table cards add column modified integer not null default 0;
+        alter table topics add column created integer not null default 0;
+        alter table topics add column modified integer not null default 0;
+        alter table incread add column created integer not null default 0;
+        alter table incread add column modified integer not null default 0;
+
+        -- a card was created no later than its first review
+        update cards set created = first.unix
+            from (select cid, min(unix) as unix from revlog group by cid) as first
+            where first.cid = cards.id;
+        -- ids only go up, so a card that was never reviewed is no newer than the next
+        -- card that was. the newest ones fall back to now.
+        update cards set created = coalesce(later.created, cast(strftime('%s', 'now') as integer))
+            from (
+                select id, min(nullif(created, 0)) over (
+                    order by id rows between 1 following and unbounded following
+                ) as created
+                from cards
+            ) as later
+            where later.id = cards.id and cards.created = 0;
+        update cards set modified = created;
+
+        -- topics and texts are no newer than the first card made from them
+        update topics set created = coalesce(
+            (select min(created) from cards where cards.topic = topics.id),
+            cast(strftime('%s', 'now') as integer)
+        );
+        update topics set modified = created;
+        update incread set created = min(
+            coalesce(skiptime, cast(strftime('%s', 'now') as integer)),
+            coalesce((select min(created) from cards where cards.source = incread.id), cast(strftime('%s', 'now') as integer))
+        );
+        update incread set modified = created;
+        ",
+    },
+    Migration {
+        version: 6,
+        description: "trash for deleted cards, topics and texts",
+        // the payload is json, so the trash doesn't need to follow later schema changes
+        sql: "
+        create table trash (
+            id          integer primary key,
+            kind        text not null,
+            description text not null,
+            deleted     integer not null,
+            payload     text not null
+        );
+        ",
+    },
+    Migration {
+        version: 7,
+        description: "tags on cards",
+        sql: "
+        create table tags (
+            id    integer primary key,
+            name  text not null unique collate nocase
+        );
+        create table card_tags (
+            card  integer not null references cards (id) on delete cascade,
+            tag   integer not null references tags (id) on delete cascade,
+            primary key (card, tag)
+        );
+        create index card_tags_tag on card_tags (tag);
+        ",
+    },
+    Migration {
+        version: 8,
+        description: "indexes on the card foreign keys, no duplicate states or dependencies",
+        sql: "
+        -- the newest row of a card wins
+        delete from finished_cards
+            where rowid not in (select max(rowid) from finished_cards group by id);
+        delete from unfinished_cards
+            where rowid not in (select max(rowid) from unfinished_cards group by id);
+        delete from pending_cards
+            where rowid not in (select max(rowid) from pending_cards group by id);
+        delete from dependencies
+            where rowid not in (select min(rowid) from dependencies group by dependent, dependency);
+
+        create unique index finished_cards_id on finished_cards (id);
+        create unique index unfinished_cards_id on unfinished_cards (id);
+        create unique index pending_cards_id on pending_cards (id);
+        create unique index dependencies_pair on dependencies (dependent, dependency);
+        create index dependencies_dependency on dependencies (dependency);
+        create index revlog_cid on revlog (cid);
+        ",
+    },
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|migration| migration.version).unwrap_or(0)
+}
+
+pub fn user_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Brings the database up to the latest schema, all migrations in one transaction.
+/// An existing database gets snapshotted into `backups` first.
+pub fn migrate(conn: &mut Connection, dbpath: &Path, backups: &Path, new_db: bool) -> anyhow::Result<()> {
+    let current = user_version(conn)?;
+    let latest = latest_version();
+    if current > latest {
+        bail!(
+            "the database at {} has schema version {}, but this version of speki only knows up to {}. Please update speki.",
+            dbpath.display(),
+            current,
+            latest
+        );
+    }
+    if current == latest {
+        return Ok(());
+    }
+
+    if !new_db {
+        snapshot(conn, backups, &format!("premigration-v{}", current))
+            .context("failed to back up the database before migrating it")?;
+    }
+
+    // tables get rebuilt, which foreign keys would get in the way of
+    conn.pragma_update(None, "foreign_keys", false)?;
+    let tx = conn.transaction()?;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        tx.execute_batch(migration.sql).with_context(|| {
+            format!(
+                "migration {} ({}) failed",
+                migration.version, migration.description
+            )
+        })?;
+    }
+    tx.pragma_update(None, "user_version", latest)?;
+    tx.commit()?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use crate::utils::aliases::*;
+use rusqlite::{params, Connection, Result};
+use std::sync::{Arc, Mutex};
+
+/// Splits what was typed into tags. Like in anki, they're separated by whitespace, and
+/// tags that only differ in case count as the same.
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = vec![];
+    for tag in text.split_whitespace() {
+        if !tags.iter().any(|known| known.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// Adds the tags to the card, making the ones that don't exist yet. Takes a bare connection
+/// so it can be used inside a transaction.
+pub fn insert_tags(conn: &Connection, card: CardID, tags: &[String]) -> Result<()> {
+    for tag in tags {
+        conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?)", [tag])?;
+        conn.execute(
+            "INSERT OR IGNORE INTO card_tags (card, tag) SELECT ?1, id FROM tags WHERE name = ?2",
+            params![card, tag],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn add_tag(conn: &Arc<Mutex<Connection>>, card: CardID, tag: &str) -> Result<()> {
+    insert_tags(&conn.lock().unwrap(), card, &[tag.to_string()])
+}
+
+/// Takes the tag off the card, and removes it altogether if no other card has it.
+pub fn remove_tag(conn: &Connection, card: CardID, tag: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM card_tags WHERE card = ?1 AND tag = (SELECT id FROM tags WHERE name = ?2)",
+        params![card, tag],
+    )?;
+    remove_unused_tags(conn)
+}
+
+fn remove_unused_tags(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "DELETE FROM tags WHERE id NOT IN (SELECT tag FROM card_tags)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Replaces the tags of the card. Tags that no card has anymore are removed.
+pub fn set_tags(conn: &Arc<Mutex<Connection>>, card: CardID, tags: &[String]) -> Result<()> {
+    let mut guard = conn.lock().unwrap();
+    let tx = guard.transaction()?;
+    tx.execute("DELETE FROM card_tags WHERE card = ?", [card])?;
+    insert_tags(&tx, card, tags)?;
+    remove_unused_tags(&tx)?;
+    tx.commit()
+}
+
+/// The tags of a card, alphabetically.
+pub fn get_tags(conn: &Arc<Mutex<Connection>>, card: CardID) -> Result<Vec<String>> {
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare(
+        "SELECT tags.name FROM card_tags JOIN tags ON tags.id = card_tags.tag
+        WHERE card_tags.card = ? ORDER BY tags.name",
+    )?;
+    let rows = stmt.query_map([card], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Every tag that's in use, alphabetically, with how many cards have it.
+pub fn all_tags(conn: &Arc<Mutex<Connection>>) -> Result<Vec<(String, u32)>> {
+    let guard = conn.lock().unwrap();
+    let mut stmt = guard.prepare(
+        "SELECT tags.name, count(*) FROM card_tags JOIN tags ON tags.id = card_tags.tag
+        GROUP BY tags.id ORDER BY tags.name",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
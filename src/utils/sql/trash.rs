@@ -0,0 +1,564 @@
+use crate::utils::aliases::*;
+use crate::utils::card::Card;
+use crate::utils::sql::tags::insert_tags;
+use chrono::{Local, TimeZone};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension, Result, Transaction};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use crate::utils::misc::unix_now;
+
+/// A card as it was when it got deleted, with everything that pointed at it.
+#[derive(Serialize, Deserialize)]
+pub struct TrashedCard {
+    id: CardID,
+    question: String,
+    answer: String,
+    frontaudio: Option<String>,
+    backaudio: Option<String>,
+    frontimg: Option<String>,
+    backimg: Option<String>,
+    cardtype: u32,
+    suspended: bool,
+    resolved: bool,
+    topic: TopicID,
+    source: IncID,
+    created: u32,
+    modified: u32,
+    /// strength and stability
+    finished: Option<(f32, f32)>,
+    /// skiptime and skipduration
+    unfinished: Option<(u32, u32)>,
+    pending: Option<u32>,
+    /// lapses and when it was flagged
+    leech: Option<(u32, u32)>,
+    note: Option<i64>,
+    dependencies: Vec<CardID>,
+    dependents: Vec<CardID>,
+    /// rowid, unix, grade, qtime and atime of every review
+    revlog: Vec<(RevlogID, u32, u32, f32, f32)>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// A topic as it was when it got deleted. Its subtopics, cards and texts moved up to its
+/// parent, restoring it takes back the ones that are still there.
+#[derive(Serialize, Deserialize)]
+pub struct TrashedTopic {
+    id: TopicID,
+    name: String,
+    parent: TopicID,
+    relpos: u32,
+    created: u32,
+    modified: u32,
+    children: Vec<TopicID>,
+    cards: Vec<CardID>,
+    sources: Vec<IncID>,
+}
+
+/// An incremental reading text as it was when it got deleted. Its extracts became top level
+/// texts and its cards lost their source, restoring it links them up again.
+#[derive(Serialize, Deserialize)]
+pub struct TrashedSource {
+    id: IncID,
+    parent: IncID,
+    topic: TopicID,
+    source: String,
+    active: bool,
+    skiptime: Option<u32>,
+    skipduration: Option<u32>,
+    row: Option<u32>,
+    column: Option<u32>,
+    created: u32,
+    modified: u32,
+    extracts: Vec<IncID>,
+    cards: Vec<CardID>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub enum Trashed {
+    Card(TrashedCard),
+    Topic(TrashedTopic),
+    Source(TrashedSource),
+}
+
+impl Trashed {
+    fn kind(&self) -> &'static str {
+        match self {
+            Trashed::Card(_) => "card",
+            Trashed::Topic(_) => "topic",
+            Trashed::Source(_) => "text",
+        }
+    }
+
+    fn description(&self) -> String {
+        let text = match self {
+            Trashed::Card(card) => &card.question,
+            Trashed::Topic(topic) => &topic.name,
+            Trashed::Source(source) => &source.source,
+        };
+        text.lines().next().unwrap_or("").chars().take(100).collect()
+    }
+}
+
+/// An entry in the trash, without the payload.
+#[derive(Clone)]
+pub struct TrashItem {
+    pub id: i64,
+    pub kind: String,
+    pub description: String,
+    pub deleted: u32,
+}
+
+impl fmt::Display for TrashItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let deleted = Local
+            .timestamp_opt(self.deleted as i64, 0)
+            .single()
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        write!(f, "{} [{}] {}", deleted, self.kind, self.description)
+    }
+}
+
+fn put_in_trash(tx: &Transaction, trashed: &Trashed) -> Result<()> {
+    let payload =
+        serde_json::to_string(trashed).map_err(|err| rusqlite::Error::ToSqlConversionFailure(Box::new(err)))?;
+    tx.execute(
+        "INSERT INTO trash (kind, description, deleted, payload) VALUES (?1, ?2, ?3, ?4)",
+        params![trashed.kind(), trashed.description(), unix_now(), payload],
+    )?;
+    Ok(())
+}
+
+fn ids(tx: &Transaction, query: &str, id: u32) -> Result<Vec<u32>> {
+    let mut stmt = tx.prepare(query)?;
+    let rows = stmt.query_map([id], |row| row.get(0))?;
+    rows.collect()
+}
+
+fn exists(tx: &Transaction, table: &str, id: u32) -> Result<bool> {
+    tx.query_row(
+        &format!("SELECT EXISTS (SELECT 1 FROM {} WHERE id = ?)", table),
+        [id],
+        |row| row.get(0),
+    )
+}
+
+/// Moves a card to the trash. Its reviews, dependencies and the rest go with it through the
+/// foreign keys, so they're saved along with it.
+pub fn trash_card(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<()> {
+    let mut guard = conn.lock().unwrap();
+    let tx = guard.transaction()?;
+    let mut card = tx.query_row(
+        "SELECT id, question, answer, frontaudio, backaudio, frontimg, backimg, cardtype, suspended, resolved, topic, source, created, modified
+        FROM cards WHERE id = ?",
+        [id],
+        |row| {
+            Ok(TrashedCard {
+                id: row.get(0)?,
+                question: row.get(1)?,
+                answer: row.get(2)?,
+                frontaudio: row.get(3)?,
+                backaudio: row.get(4)?,
+                frontimg: row.get(5)?,
+                backimg: row.get(6)?,
+                cardtype: row.get(7)?,
+                suspended: row.get(8)?,
+                resolved: row.get(9)?,
+                topic: row.get(10)?,
+                source: row.get(11)?,
+                created: row.get(12)?,
+                modified: row.get(13)?,
+                finished: None,
+                unfinished: None,
+                pending: None,
+                leech: None,
+                note: None,
+                dependencies: vec![],
+                dependents: vec![],
+                revlog: vec![],
+                tags: vec![],
+            })
+        },
+    )?;
+    card.finished = tx
+        .query_row(
+            "SELECT strength, stability FROM finished_cards WHERE id = ?",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    card.unfinished = tx
+        .query_row(
+            "SELECT skiptime, skipduration FROM unfinished_cards WHERE id = ?",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?;
+    card.pending = tx
+        .query_row("SELECT position FROM pending_cards WHERE id = ?", [id], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    card.leech = tx
+        .query_row("SELECT lapses, flagged FROM leeches WHERE id = ?", [id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()?;
+    card.note = tx
+        .query_row("SELECT note FROM anki_notes WHERE cid = ?", [id], |row| {
+            row.get(0)
+        })
+        .optional()?;
+    card.dependencies = ids(
+        &tx,
+        "SELECT dependency FROM dependencies WHERE dependent = ?",
+        id,
+    )?;
+    card.dependents = ids(
+        &tx,
+        "SELECT dependent FROM dependencies WHERE dependency = ?",
+        id,
+    )?;
+    {
+        let mut stmt = tx.prepare(
+            "SELECT rowid, unix, grade, qtime, atime FROM revlog WHERE cid = ? ORDER BY unix",
+        )?;
+        let rows = stmt.query_map([id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+        })?;
+        card.revlog = rows.collect::<Result<_>>()?;
+    }
+    {
+        let mut stmt = tx.prepare(
+            "SELECT tags.name FROM card_tags JOIN tags ON tags.id = card_tags.tag WHERE card_tags.card = ?",
+        )?;
+        let rows = stmt.query_map([id], |row| row.get(0))?;
+        card.tags = rows.collect::<Result<_>>()?;
+    }
+    put_in_trash(&tx, &Trashed::Card(card))?;
+    tx.execute("DELETE FROM cards WHERE id = ?", [id])?;
+    tx.commit()
+}
+
+/// Moves a topic to the trash. The caller has already moved its subtopics up to its parent,
+/// `children` are those subtopics in order so they can be taken back on restore.
+pub fn trash_topic(conn: &Arc<Mutex<Connection>>, id: TopicID, children: &[TopicID]) -> Result<()> {
+    let mut guard = conn.lock().unwrap();
+    let tx = guard.transaction()?;
+    let mut topic = tx.query_row(
+        "SELECT id, name, parent, relpos, created, modified FROM topics WHERE id = ?",
+        [id],
+        |row| {
+            Ok(TrashedTopic {
+                id: row.get(0)?,
+                name: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                parent: row.get(2)?,
+                relpos: row.get(3)?,
+                created: row.get(4)?,
+                modified: row.get(5)?,
+                children: children.to_vec(),
+                cards: vec![],
+                sources: vec![],
+            })
+        },
+    )?;
+    topic.cards = ids(&tx, "SELECT id FROM cards WHERE topic = ?", id)?;
+    topic.sources = ids(&tx, "SELECT id FROM incread WHERE topic = ?", id)?;
+    put_in_trash(&tx, &Trashed::Topic(topic))?;
+    // the cards and texts move up to the parent by a trigger
+    tx.execute("DELETE FROM topics WHERE id = ?", [id])?;
+    tx.commit()
+}
+
+/// Moves an incremental reading text to the trash, its extracts and cards stay.
+pub fn trash_source(conn: &Arc<Mutex<Connection>>, id: IncID) -> Result<()> {
+    let mut guard = conn.lock().unwrap();
+    let tx = guard.transaction()?;
+    let mut source = tx.query_row(
+        "SELECT id, parent, topic, source, active, skiptime, skipduration, row, column, created, modified
+        FROM incread WHERE id = ?",
+        [id],
+        |row| {
+            Ok(TrashedSource {
+                id: row.get(0)?,
+                parent: row.get(1)?,
+                topic: row.get(2)?,
+                source: row.get(3)?,
+                active: row.get(4)?,
+                skiptime: row.get(5)?,
+                skipduration: row.get(6)?,
+                row: row.get(7)?,
+                column: row.get(8)?,
+                created: row.get(9)?,
+                modified: row.get(10)?,
+                extracts: vec![],
+                cards: vec![],
+            })
+        },
+    )?;
+    source.extracts = ids(&tx, "SELECT id FROM incread WHERE parent = ?", id)?;
+    source.cards = ids(&tx, "SELECT id FROM cards WHERE source = ?", id)?;
+    put_in_trash(&tx, &Trashed::Source(source))?;
+    tx.execute("UPDATE incread SET parent = 0 WHERE parent = ?", [id])?;
+    tx.execute("UPDATE cards SET source = 0 WHERE source = ?", [id])?;
+    tx.execute("DELETE FROM incread WHERE id = ?", [id])?;
+    tx.commit()
+}
+
+/// Everything in the trash, most recently deleted first.
+pub fn load_trash(conn: &Arc<Mutex<Connection>>) -> Result<Vec<TrashItem>> {
+    let guard = conn.lock().unwrap();
+    let mut stmt =
+        guard.prepare("SELECT id, kind, description, deleted FROM trash ORDER BY deleted DESC, id DESC")?;
+    let rows = stmt.query_map([], |row| {
+        Ok(TrashItem {
+            id: row.get(0)?,
+            kind: row.get(1)?,
+            description: row.get(2)?,
+            deleted: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Deletes an entry from the trash for good.
+pub fn purge_item(conn: &Arc<Mutex<Connection>>, id: i64) -> Result<()> {
+    conn.lock()
+        .unwrap()
+        .execute("DELETE FROM trash WHERE id = ?", [id])?;
+    Ok(())
+}
+
+/// Deletes everything that's been in the trash for longer than `days`, returns how many.
+pub fn purge_trash(conn: &Arc<Mutex<Connection>>, days: u32) -> Result<usize> {
+    let cutoff = unix_now().saturating_sub(days.saturating_mul(86400));
+    conn.lock()
+        .unwrap()
+        .execute("DELETE FROM trash WHERE deleted < ?", [cutoff])
+}
+
+/// Puts an entry from the trash back where it was, under its old id unless that's been taken
+/// in the meantime. Links to cards, topics and texts that are gone by now are dropped.
+pub fn restore(conn: &Arc<Mutex<Connection>>, id: i64) -> crate::error::Result<()> {
+    let mut resolve = vec![];
+    {
+        let mut guard = conn.lock().unwrap();
+        let tx = guard.transaction()?;
+        let payload: String =
+            tx.query_row("SELECT payload FROM trash WHERE id = ?", [id], |row| row.get(0))?;
+        let trashed: Trashed = serde_json::from_str(&payload).map_err(|err| {
+            rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(err))
+        })?;
+        match trashed {
+            Trashed::Card(card) => resolve = restore_card(&tx, card)?,
+            Trashed::Topic(topic) => restore_topic(&tx, topic)?,
+            Trashed::Source(source) => restore_source(&tx, source)?,
+        }
+        tx.execute("DELETE FROM trash WHERE id = ?", [id])?;
+        tx.commit()?;
+    }
+    for id in resolve {
+        Card::check_resolved(id, conn)?;
+    }
+    Ok(())
+}
+
+/// Returns the restored card and its dependents, whose resolved status might have changed.
+fn restore_card(tx: &Transaction, card: TrashedCard) -> Result<Vec<CardID>> {
+    let old_id = (!exists(tx, "cards", card.id)?).then_some(card.id);
+    let topic = if exists(tx, "topics", card.topic)? {
+        card.topic
+    } else {
+        1
+    };
+    let source = if exists(tx, "incread", card.source)? {
+        card.source
+    } else {
+        0
+    };
+    tx.execute(
+        "INSERT INTO cards (id, question, answer, frontaudio, backaudio, frontimg, backimg, cardtype, suspended, resolved, topic, source, created, modified)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        params![
+            old_id,
+            card.question,
+            card.answer,
+            card.frontaudio,
+            card.backaudio,
+            card.frontimg,
+            card.backimg,
+            card.cardtype,
+            card.suspended,
+            card.resolved,
+            topic,
+            source,
+            card.created,
+            card.modified,
+        ],
+    )?;
+    let id = tx.last_insert_rowid() as CardID;
+
+    if let Some((strength, stability)) = card.finished {
+        tx.execute(
+            "INSERT INTO finished_cards (id, strength, stability) VALUES (?1, ?2, ?3)",
+            params![id, strength, stability],
+        )?;
+    }
+    if let Some((skiptime, skipduration)) = card.unfinished {
+        tx.execute(
+            "INSERT INTO unfinished_cards (id, skiptime, skipduration) VALUES (?1, ?2, ?3)",
+            params![id, skiptime, skipduration],
+        )?;
+    }
+    if let Some(position) = card.pending {
+        tx.execute(
+            "INSERT INTO pending_cards (id, position) VALUES (?1, ?2)",
+            params![id, position],
+        )?;
+    }
+    if let Some((lapses, flagged)) = card.leech {
+        tx.execute(
+            "INSERT INTO leeches (id, lapses, flagged) VALUES (?1, ?2, ?3)",
+            params![id, lapses, flagged],
+        )?;
+    }
+    if let Some(note) = card.note {
+        tx.execute(
+            "INSERT OR REPLACE INTO anki_notes (cid, note) VALUES (?1, ?2)",
+            params![id, note],
+        )?;
+    }
+    for (rowid, unix, grade, qtime, atime) in card.revlog {
+        let taken: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM revlog WHERE rowid = ?)",
+            [rowid],
+            |row| row.get(0),
+        )?;
+        tx.execute(
+            "INSERT INTO revlog (rowid, unix, cid, grade, qtime, atime) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![(!taken).then_some(rowid), unix, id, grade, qtime, atime],
+        )?;
+    }
+    for dependency in card.dependencies {
+        if exists(tx, "cards", dependency)? {
+            tx.execute(
+                "INSERT OR IGNORE INTO dependencies (dependent, dependency) VALUES (?1, ?2)",
+                params![id, dependency],
+            )?;
+        }
+    }
+    insert_tags(tx, id, &card.tags)?;
+    let mut resolve = vec![id];
+    for dependent in card.dependents {
+        if exists(tx, "cards", dependent)? {
+            tx.execute(
+                "INSERT OR IGNORE INTO dependencies (dependent, dependency) VALUES (?1, ?2)",
+                params![dependent, id],
+            )?;
+            resolve.push(dependent);
+        }
+    }
+    Ok(resolve)
+}
+
+fn restore_topic(tx: &Transaction, topic: TrashedTopic) -> Result<()> {
+    let parent = if exists(tx, "topics", topic.parent)? {
+        topic.parent
+    } else {
+        1
+    };
+    let old_id = (!exists(tx, "topics", topic.id)?).then_some(topic.id);
+    tx.execute(
+        "INSERT INTO topics (id, name, parent, relpos, created, modified) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![old_id, topic.name, parent, topic.relpos, topic.created, topic.modified],
+    )?;
+    let id = tx.last_insert_rowid() as TopicID;
+
+    // subtopics that are still where they were left get moved back in, in their old order
+    let mut relpos = 0;
+    for child in topic.children {
+        let moved = tx.execute(
+            "UPDATE topics SET parent = ?1, relpos = ?2 WHERE id = ?3 AND parent = ?4",
+            params![id, relpos, child, parent],
+        )?;
+        relpos += moved as u32;
+    }
+
+    // then the topic takes its old place among its siblings, as far as they still go
+    let mut siblings = {
+        let mut stmt =
+            tx.prepare("SELECT id FROM topics WHERE parent = ?1 AND id != ?2 ORDER BY relpos")?;
+        let rows = stmt.query_map(params![parent, id], |row| row.get::<_, TopicID>(0))?;
+        rows.collect::<Result<Vec<_>>>()?
+    };
+    let position = std::cmp::min(topic.relpos as usize, siblings.len());
+    siblings.insert(position, id);
+    for (relpos, sibling) in siblings.into_iter().enumerate() {
+        tx.execute(
+            "UPDATE topics SET relpos = ? WHERE id = ?",
+            params![relpos as u32, sibling],
+        )?;
+    }
+
+    for card in topic.cards {
+        tx.execute(
+            "UPDATE cards SET topic = ?1 WHERE id = ?2 AND topic = ?3",
+            params![id, card, parent],
+        )?;
+    }
+    for source in topic.sources {
+        tx.execute(
+            "UPDATE incread SET topic = ?1 WHERE id = ?2 AND topic = ?3",
+            params![id, source, parent],
+        )?;
+    }
+    Ok(())
+}
+
+fn restore_source(tx: &Transaction, source: TrashedSource) -> Result<()> {
+    let topic = if exists(tx, "topics", source.topic)? {
+        source.topic
+    } else {
+        1
+    };
+    let parent = if exists(tx, "incread", source.parent)? {
+        source.parent
+    } else {
+        0
+    };
+    let old_id = (!exists(tx, "incread", source.id)?).then_some(source.id);
+    tx.execute(
+        "INSERT INTO incread (id, parent, topic, source, active, skiptime, skipduration, row, column, created, modified)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            old_id,
+            parent,
+            topic,
+            source.source,
+            source.active,
+            source.skiptime,
+            source.skipduration,
+            source.row,
+            source.column,
+            source.created,
+            source.modified,
+        ],
+    )?;
+    let id = tx.last_insert_rowid() as IncID;
+    for extract in source.extracts {
+        tx.execute(
+            "UPDATE incread SET parent = ?1 WHERE id = ?2 AND parent = 0",
+            params![id, extract],
+        )?;
+    }
+    for card in source.cards {
+        tx.execute(
+            "UPDATE cards SET source = ?1 WHERE id = ?2 AND source = 0",
+            params![id, card],
+        )?;
+    }
+    Ok(())
+}
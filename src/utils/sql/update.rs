@@ -1,32 +1,35 @@
 use super::fetch::*;
 use crate::utils::aliases::*;
-use crate::utils::card::CardType;
+use crate::utils::card::{CardState, CardType};
+use crate::utils::leech::LEECH_TAG;
+use crate::utils::sql::tags::remove_tag;
 use crate::widgets::textinput::CursorPos;
 use rand::prelude::*;
 use rusqlite::{params, Connection, Result};
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::utils::misc::unix_now;
 
+/// Only counts as a modification if the text actually changed, the review tab writes
+/// question and answer back after every card.
 pub fn update_card_question(conn: &Arc<Mutex<Connection>>, id: u32, name: String) -> Result<()> {
     conn.lock()
         .unwrap()
-        .prepare("UPDATE cards SET question = ? WHERE id = ?")?
-        .execute(params![name, id])?;
+        .prepare("UPDATE cards SET modified = CASE WHEN question = ?1 THEN modified ELSE ?2 END, question = ?1 WHERE id = ?3")?
+        .execute(params![name, unix_now(), id])?;
     Ok(())
 }
 pub fn update_card_answer(conn: &Arc<Mutex<Connection>>, id: u32, name: String) -> Result<()> {
     conn.lock()
         .unwrap()
-        .prepare("UPDATE cards SET answer = ? WHERE id = ?")?
-        .execute(params![name, id])?;
+        .prepare("UPDATE cards SET modified = CASE WHEN answer = ?1 THEN modified ELSE ?2 END, answer = ?1 WHERE id = ?3")?
+        .execute(params![name, unix_now(), id])?;
     Ok(())
 }
 
 pub fn update_strength(conn: &Arc<Mutex<Connection>>, id: CardID, strength: f32) -> Result<()> {
     conn.lock()
         .unwrap()
-        .prepare("UPDATE finished_cards SET strength = ? WHERE id = ?")
-        .unwrap()
+        .prepare("UPDATE finished_cards SET strength = ? WHERE id = ?")?
         .execute(params![strength, id])?;
     Ok(())
 }
@@ -34,8 +37,7 @@ pub fn update_strength(conn: &Arc<Mutex<Connection>>, id: CardID, strength: f32)
 pub fn set_stability(conn: &Arc<Mutex<Connection>>, id: CardID, stability: f32) -> Result<()> {
     conn.lock()
         .unwrap()
-        .prepare("UPDATE finished_cards SET stability = ? WHERE id = ?")
-        .unwrap()
+        .prepare("UPDATE finished_cards SET stability = ? WHERE id = ?")?
         .execute(params![stability, id])?;
     Ok(())
 }
@@ -49,25 +51,20 @@ pub fn set_cardtype(conn: &Arc<Mutex<Connection>>, id: CardID, cardtype: CardTyp
 
     conn.lock()
         .unwrap()
-        .prepare("UPDATE cards SET cardtype = ? WHERE id = ?")
-        .unwrap()
+        .prepare("UPDATE cards SET cardtype = ? WHERE id = ?")?
         .execute(params![cardtype, id])?;
     Ok(())
 }
 pub fn set_suspended(conn: &Arc<Mutex<Connection>>, id: CardID, suspended: bool) -> Result<()> {
     conn.lock()
         .unwrap()
-        .prepare("UPDATE cards SET suspended = ? WHERE id = ?")
-        .unwrap()
+        .prepare("UPDATE cards SET suspended = ? WHERE id = ?")?
         .execute(params![suspended, id])?;
     Ok(())
 }
 
-pub fn set_resolved(conn: &Arc<Mutex<Connection>>, id: CardID, resolved: bool) -> Result<()> {
-    conn.lock()
-        .unwrap()
-        .prepare("UPDATE cards SET resolved = ? WHERE id = ?")
-        .unwrap()
+pub fn set_resolved(conn: &Connection, id: CardID, resolved: bool) -> Result<()> {
+    conn.prepare("UPDATE cards SET resolved = ? WHERE id = ?")?
         .execute(params![resolved, id])?;
     Ok(())
 }
@@ -75,52 +72,35 @@ pub fn set_resolved(conn: &Arc<Mutex<Connection>>, id: CardID, resolved: bool) -
 pub fn update_topic_name(conn: &Arc<Mutex<Connection>>, id: u32, name: String) -> Result<()> {
     conn.lock()
         .unwrap()
-        .prepare("UPDATE topics SET name = ? WHERE id = ?")
-        .unwrap()
-        .execute(params![name, id])?;
+        .prepare("UPDATE topics SET name = ?, modified = ? WHERE id = ?")?
+        .execute(params![name, unix_now(), id])?;
     Ok(())
 }
 
 pub fn update_topic_relpos(conn: &Arc<Mutex<Connection>>, id: u32, relpos: u32) -> Result<()> {
     conn.lock()
         .unwrap()
-        .prepare("UPDATE topics SET relpos = ? WHERE id = ?")
-        .unwrap()
-        .execute(params![relpos, id])?;
+        .prepare("UPDATE topics SET relpos = ?, modified = ? WHERE id = ?")?
+        .execute(params![relpos, unix_now(), id])?;
     Ok(())
 }
 
-pub fn update_card_source(
-    conn: &Arc<Mutex<Connection>>,
-    cardid: CardID,
-    incid: IncID,
-) -> Result<()> {
-    conn.lock()
-        .unwrap()
-        .prepare("UPDATE cards SET source = ? WHERE id = ?")
-        .unwrap()
-        .execute(params![incid, cardid])?;
+pub fn update_card_source(conn: &Connection, cardid: CardID, incid: IncID) -> Result<()> {
+    conn.prepare("UPDATE cards SET source = ?, modified = ? WHERE id = ?")?
+        .execute(params![incid, unix_now(), cardid])?;
     Ok(())
 }
-pub fn update_card_topic(
-    conn: &Arc<Mutex<Connection>>,
-    old_topic: u32,
-    new_topic: u32,
-) -> Result<()> {
-    conn.lock()
-        .unwrap()
-        .prepare("UPDATE cards SET topic = ? WHERE topic = ?")
-        .unwrap()
-        .execute(params![new_topic, old_topic])?;
+pub fn update_card_topic(conn: &Connection, old_topic: u32, new_topic: u32) -> Result<()> {
+    conn.prepare("UPDATE cards SET topic = ?, modified = ? WHERE topic = ?")?
+        .execute(params![new_topic, unix_now(), old_topic])?;
     Ok(())
 }
 
 pub fn update_topic_parent(conn: &Arc<Mutex<Connection>>, id: u32, parent: u32) -> Result<()> {
     conn.lock()
         .unwrap()
-        .prepare("UPDATE topics SET parent = ? WHERE id = ?")
-        .unwrap()
-        .execute(params![parent, id])?;
+        .prepare("UPDATE topics SET parent = ?, modified = ? WHERE id = ?")?
+        .execute(params![parent, unix_now(), id])?;
     Ok(())
 }
 
@@ -133,75 +113,126 @@ pub fn update_inc_text(
     let row = cursor.row;
     let column = cursor.column;
 
+    // saving the cursor position alone doesn't count as a modification
     conn.lock()
         .unwrap()
-        .prepare("UPDATE incread SET source = ?1, row = ?2, column = ?3 WHERE id = ?4")
-        .unwrap()
-        .execute(params![source, row, column, id])?;
+        .prepare("UPDATE incread SET modified = CASE WHEN source = ?1 THEN modified ELSE ?5 END, source = ?1, row = ?2, column = ?3 WHERE id = ?4")?
+        .execute(params![source, row, column, id, unix_now()])?;
     Ok(())
 }
 
 pub fn update_inc_active(conn: &Arc<Mutex<Connection>>, id: IncID, active: bool) -> Result<()> {
     conn.lock()
         .unwrap()
-        .prepare("UPDATE incread SET active = ? WHERE id = ?")
-        .unwrap()
-        .execute(params![active, id])?;
+        .prepare("UPDATE incread SET active = ?, modified = ? WHERE id = ?")?
+        .execute(params![active, unix_now(), id])?;
     Ok(())
 }
 
 pub fn double_skip_duration(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<()> {
-    update_skiptime(conn, id).unwrap();
+    update_skiptime(conn, id)?;
     let mut rng = rand::thread_rng();
     let mut y: f64 = rng.gen();
     y += 0.5; // y is now between 0.5 and 1.5
-    let skipduration = get_skipduration(conn, id).unwrap();
+    let skipduration = get_skipduration(conn, id)?;
     let new_skipduration = std::cmp::max((skipduration as f64 * y * 2.0) as u32, 2);
     conn.lock()
         .unwrap()
-        .prepare("UPDATE unfinished_cards SET skipduration = ? WHERE id = ?")
-        .unwrap()
+        .prepare("UPDATE unfinished_cards SET skipduration = ? WHERE id = ?")?
         .execute(params![new_skipduration, id])?;
     Ok(())
 }
 
 pub fn update_skiptime(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<()> {
-    let unix = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as u32;
+    let unix = unix_now();
     conn.lock()
         .unwrap()
-        .prepare("UPDATE unfinished_cards SET skiptime = ? WHERE id = ?")
-        .unwrap()
+        .prepare("UPDATE unfinished_cards SET skiptime = ? WHERE id = ?")?
         .execute(params![unix, id])?;
     Ok(())
 }
 
 pub fn double_inc_skip_duration(conn: &Arc<Mutex<Connection>>, id: IncID) -> Result<()> {
-    update_inc_skiptime(conn, id).unwrap();
+    update_inc_skiptime(conn, id)?;
     let mut rng = rand::thread_rng();
     let mut y: f64 = rng.gen();
     y += 0.5; // y is now between 0.5 and 1.5
-    let skipduration = get_inc_skipduration(conn, id).unwrap();
+    let skipduration = get_inc_skipduration(conn, id)?;
     let new_skipduration = std::cmp::max((skipduration as f64 * y * 2.0) as u32, 2);
     conn.lock()
         .unwrap()
-        .prepare("UPDATE incread SET skipduration = ? WHERE id = ?")
-        .unwrap()
+        .prepare("UPDATE incread SET skipduration = ? WHERE id = ?")?
         .execute(params![new_skipduration, id])?;
     Ok(())
 }
 
 pub fn update_inc_skiptime(conn: &Arc<Mutex<Connection>>, id: IncID) -> Result<()> {
-    let unix = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as u32;
+    let unix = unix_now();
     conn.lock()
         .unwrap()
-        .prepare("UPDATE incread SET skiptime = ? WHERE id = ?")
-        .unwrap()
+        .prepare("UPDATE incread SET skiptime = ? WHERE id = ?")?
         .execute(params![unix, id])?;
     Ok(())
 }
+
+/// Puts a card back into the state it was in when the snapshot was taken. If that takes away
+/// its leech flag, the leech tag it got along with it goes too.
+pub fn restore_card_state(conn: &Arc<Mutex<Connection>>, id: CardID, state: &CardState) -> Result<()> {
+    let cardtype = match state.cardtype {
+        CardType::Pending => 0,
+        CardType::Unfinished => 1,
+        CardType::Finished => 2,
+    };
+    let mut guard = conn.lock().unwrap();
+    let tx = guard.transaction()?;
+    tx.execute(
+        "UPDATE cards SET cardtype = ?, suspended = ? WHERE id = ?",
+        params![cardtype, state.suspended, id],
+    )?;
+    tx.execute("DELETE FROM finished_cards WHERE id = ?", [id])?;
+    tx.execute("DELETE FROM unfinished_cards WHERE id = ?", [id])?;
+    tx.execute("DELETE FROM pending_cards WHERE id = ?", [id])?;
+    let unflagged = tx.execute("DELETE FROM leeches WHERE id = ?", [id])? > 0;
+    if unflagged && state.leech.is_none() {
+        remove_tag(&tx, id, LEECH_TAG)?;
+    }
+    if let Some((strength, stability)) = state.finished {
+        tx.execute(
+            "INSERT INTO finished_cards (id, strength, stability) VALUES (?1, ?2, ?3)",
+            params![id, strength, stability],
+        )?;
+    }
+    if let Some((skiptime, skipduration)) = state.unfinished {
+        tx.execute(
+            "INSERT INTO unfinished_cards (id, skiptime, skipduration) VALUES (?1, ?2, ?3)",
+            params![id, skiptime, skipduration],
+        )?;
+    }
+    if let Some(position) = state.pending {
+        tx.execute(
+            "INSERT INTO pending_cards (id, position) VALUES (?1, ?2)",
+            params![id, position],
+        )?;
+    }
+    if let Some((lapses, flagged)) = state.leech {
+        tx.execute(
+            "INSERT INTO leeches (id, lapses, flagged) VALUES (?1, ?2, ?3)",
+            params![id, lapses, flagged],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Writes strength and stability for many cards at once, either all of them or none.
+pub fn set_memory_states(conn: &Arc<Mutex<Connection>>, states: &[(CardID, f32, f32)]) -> Result<()> {
+    let mut guard = conn.lock().unwrap();
+    let tx = guard.transaction()?;
+    {
+        let mut stmt =
+            tx.prepare("UPDATE finished_cards SET strength = ?, stability = ? WHERE id = ?")?;
+        for (id, strength, stability) in states {
+            stmt.execute(params![strength, stability, id])?;
+        }
+    }
+    tx.commit()
+}
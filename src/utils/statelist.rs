@@ -34,17 +34,17 @@ impl<T: Display> StatefulList<T> {
         }
     }
 
-    pub fn load_cards(conn: &Arc<Mutex<Connection>>) -> StatefulList<u32> {
-        let cardvec = load_cards(&conn).unwrap();
+    pub fn load_cards(conn: &Arc<Mutex<Connection>>) -> rusqlite::Result<StatefulList<u32>> {
+        let cardvec = load_cards(&conn)?;
         let mut items = Vec::<u32>::new();
         for card in cardvec {
             items.push(card.id);
         }
 
-        StatefulList {
+        Ok(StatefulList {
             state: ListState::default(),
             items,
-        }
+        })
     }
 
     pub fn load_empty() -> StatefulList<u32> {
@@ -1,8 +1,9 @@
+use crate::error::Result;
 use crate::utils::statelist::StatefulList;
 use crate::utils::{
     aliases::*,
     card::Card,
-    sql::{fetch::load_card_matches, insert::update_both},
+    sql::{fetch::CardQuery, insert::update_both},
 };
 use crate::widgets::textinput::Field;
 use rusqlite::Connection;
@@ -45,39 +46,47 @@ pub enum CardPurpose {
 }
 
 impl FindCardWidget {
-    pub fn new(conn: &Arc<Mutex<Connection>>, prompt: String, purpose: CardPurpose) -> Self {
+    pub fn new(
+        conn: &Arc<Mutex<Connection>>,
+        prompt: String,
+        purpose: CardPurpose,
+    ) -> Result<Self> {
         let mut list = StatefulList::<CardMatch>::new();
         let searchterm = Field::new();
-        list.reset_filter(conn, searchterm.return_text());
+        list.reset_filter(conn, searchterm.return_text())?;
 
         let status = PopUpStatus::OnGoing;
 
-        FindCardWidget {
+        Ok(FindCardWidget {
             prompt,
             searchterm,
             list,
             status,
             purpose,
-        }
+        })
     }
 
-    pub fn keyhandler(&mut self, conn: &Arc<Mutex<Connection>>, key: MyKey) -> PopUpStatus {
+    pub fn keyhandler(
+        &mut self,
+        conn: &Arc<Mutex<Connection>>,
+        key: MyKey,
+    ) -> Result<PopUpStatus> {
         match key {
-            MyKey::Enter => self.complete(conn),
+            MyKey::Enter => self.complete(conn)?,
             MyKey::Esc => self.status = PopUpStatus::Finished,
             MyKey::Down => self.list.next(),
             MyKey::Up => self.list.previous(),
             key => {
                 self.searchterm.keyhandler(key);
-                self.list.reset_filter(conn, self.searchterm.return_text());
+                self.list.reset_filter(conn, self.searchterm.return_text())?;
             }
         }
-        self.status.clone()
+        Ok(self.status.clone())
     }
 
-    fn complete(&mut self, conn: &Arc<Mutex<Connection>>) {
+    fn complete(&mut self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
         if self.list.state.selected().is_none() {
-            return;
+            return Ok(());
         }
 
         let idx = self.list.state.selected().unwrap();
@@ -85,18 +94,19 @@ impl FindCardWidget {
 
         match self.purpose {
             CardPurpose::NewDependent(source_id) => {
-                update_both(conn, chosen_id, source_id).unwrap();
-                Card::check_resolved(chosen_id, conn);
+                update_both(conn, chosen_id, source_id)?;
+                Card::check_resolved(chosen_id, conn)?;
             }
             CardPurpose::NewDependency(source_id) => {
-                update_both(conn, source_id, chosen_id).unwrap();
-                Card::check_resolved(source_id, conn);
+                update_both(conn, source_id, chosen_id)?;
+                Card::check_resolved(source_id, conn)?;
             }
             CardPurpose::NewCloze(_topic_id) => {
                 todo!();
             }
         }
         self.status = PopUpStatus::Finished;
+        Ok(())
     }
 
     pub fn render(&mut self, f: &mut Frame<MyType>, area: Rect) {
@@ -121,10 +131,16 @@ impl FindCardWidget {
 }
 
 impl StatefulList<CardMatch> {
-    pub fn reset_filter(&mut self, conn: &Arc<Mutex<Connection>>, mut searchterm: String) {
+    pub fn reset_filter(
+        &mut self,
+        conn: &Arc<Mutex<Connection>>,
+        searchterm: String,
+    ) -> rusqlite::Result<()> {
         let mut matching_cards = Vec::<CardMatch>::new();
-        searchterm.pop();
-        let all_cards = load_card_matches(conn, &searchterm).unwrap();
+        let all_cards = CardQuery::default()
+            .search(&searchterm)
+            .limit(50)
+            .fetch_carditems(conn)?;
         for card in all_cards {
             matching_cards.push(CardMatch {
                 question: card.question,
@@ -132,6 +148,7 @@ impl StatefulList<CardMatch> {
             });
         }
         self.items = matching_cards;
+        Ok(())
     }
 
     pub fn choose_card(&self) -> u32 {
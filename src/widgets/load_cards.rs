@@ -7,10 +7,12 @@ use crate::{Direction, SpekiPaths};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
+use crate::utils::sql::insert::save_cards;
+use crate::utils::sql::tags::parse_tags;
 use crate::utils::card::CardType;
 use crate::utils::{aliases::*, card};
 use crate::MyType;
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use rusqlite::Connection;
 use std::fs;
 use tui::widgets::ListState;
@@ -25,19 +27,30 @@ use tui::{
     widgets::{Block, Borders, List, ListItem},
 };
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 #[derive(Clone, Debug)]
 pub struct ImportProgress {
     pub curr_index: usize,
     pub total: usize,
+    pub status: ImportStatus,
     //   pub front: String,
     //   pub back: String,
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImportStatus {
+    Ongoing,
+    Done,
+    Cancelled,
+    Failed(String),
+}
+
 pub enum UnzipStatus {
     Ongoing(String),
-    Done,
+    Done(Template),
+    Failed(String),
 }
 
 #[derive(Clone, Debug)]
@@ -111,6 +124,7 @@ struct Kort {
 struct Note {
     model_id: ModelID,
     fields: Vec<CardField>,
+    tags: Vec<String>,
 }
 #[derive(Default, Clone, Debug)]
 struct Model {
@@ -230,7 +244,7 @@ pub struct Template {
 
 
 impl Template {
-    pub fn new(conn: &Arc<Mutex<Connection>>, deckname: String, paths: &SpekiPaths) -> Template {
+    pub fn new(conn: &Arc<Mutex<Connection>>, deckname: String, paths: &SpekiPaths) -> Result<Template> {
         let cards = vec![];
         let notes: HashMap<NoteID, Note> = HashMap::new();
         let models: HashMap<ModelID, Model> = HashMap::new();
@@ -247,10 +261,10 @@ impl Template {
             selected: Selected::Preview,
             state: LoadState::OnGoing,
         };
-        temp.init(&deckname, paths);
+        temp.init(&deckname, paths)?;
         temp.front_view.stickytitle = true;
         temp.back_view.stickytitle = true;
-        temp
+        Ok(temp)
     }
 
    
@@ -344,8 +358,8 @@ impl Template {
         mediapath.push(format!("{}/", deckname));
         let mut medianames = mediapath.clone();
         medianames.push("media");
-        let contents = fs::read_to_string(&medianames).unwrap();
-        let jsonmodels: serde_json::Value = serde_json::from_str(&contents).unwrap();
+        let contents = fs::read_to_string(&medianames)?;
+        let jsonmodels: serde_json::Value = serde_json::from_str(&contents)?;
         if let serde_json::Value::Object(ob) = jsonmodels {
             for (key, val) in ob {
                 let mut val = val.to_string();
@@ -360,44 +374,48 @@ impl Template {
                 std::fs::rename(keypath, valpath)?;
             }
         } else {
-            panic!();
+            bail!("the media list of the deck is malformed");
         }
         Ok(())
     }
 
+    /// Unzips a downloaded deck and loads its template, meant to run on its own thread.
+    /// Progress and the outcome are sent back instead of panicking, so the ui can show them.
     pub fn unzip_deck(
+        conn: Arc<Mutex<Connection>>,
         paths: SpekiPaths,
         deckname: String,
         transmitter: std::sync::mpsc::Sender<UnzipStatus>,
-    ) -> PathBuf {
+    ) {
+        let status = match Self::unzip(&conn, &paths, deckname, &transmitter) {
+            Ok(template) => UnzipStatus::Done(template),
+            Err(err) => UnzipStatus::Failed(format!("{:#}", err)),
+        };
+        let _ = transmitter.send(status);
+    }
+
+    fn unzip(
+        conn: &Arc<Mutex<Connection>>,
+        paths: &SpekiPaths,
+        deckname: String,
+        transmitter: &std::sync::mpsc::Sender<UnzipStatus>,
+    ) -> Result<Template> {
         let mut folderpath = paths.media.clone();
         folderpath.push(format!("{}/", &deckname));
-
-        if !std::path::Path::new(&paths.media).exists() {
-            std::fs::create_dir(&paths.media).unwrap();
-        }
-
-        if !std::path::Path::new(&folderpath).exists() {
-            std::fs::create_dir(&folderpath).unwrap();
-        }
-
-        let mut unzipped_db = paths.media.clone();
-        unzipped_db.push(format!("{}/collection.anki2", &deckname));
+        std::fs::create_dir_all(&folderpath)?;
 
         let _ = transmitter.send(UnzipStatus::Ongoing("Opening zip file".to_string()));
-        let file = fs::File::open(&paths.downloc).expect(&format!(
-            "couldnt open file: {}",
-            &paths.downloc.to_str().unwrap()
-        ));
+        let file = fs::File::open(&paths.downloc)
+            .with_context(|| format!("couldn't open {}", paths.downloc.display()))?;
         let _ = transmitter.send(UnzipStatus::Ongoing(
             "Loading zip file to memory".to_string(),
         ));
-        let mut archive = zip::ZipArchive::new(file).unwrap();
+        let mut archive = zip::ZipArchive::new(file)?;
         let _ = transmitter.send(UnzipStatus::Ongoing("Extracting files...".to_string()));
-        archive.extract(folderpath).unwrap();
+        archive.extract(folderpath)?;
         let _ = transmitter.send(UnzipStatus::Ongoing("Preparing files...".to_string()));
-        Self::rename_media(&deckname, &paths).unwrap();
-        unzipped_db
+        Self::rename_media(&deckname, paths)?;
+        Self::new(conn, deckname, paths)
     }
 
     fn selected_model(&mut self) -> &mut Model {
@@ -429,14 +447,22 @@ impl Template {
             .afmt = self.back_template.return_text();
     }
 
-    fn init(&mut self, deckname: &String, paths: &SpekiPaths) {
+    fn init(&mut self, deckname: &String, paths: &SpekiPaths) -> Result<()> {
         let mut deckdb = paths.media.clone();
         deckdb.push(format!("{}/collection.anki2", &deckname));
-        let ankon = Arc::new(Mutex::new(Connection::open(deckdb).unwrap()));
-        self.load_models(&ankon);
-        self.load_notes(&ankon, deckname, paths).unwrap();
-        self.load_cards(&ankon).unwrap();
+        let ankon = Arc::new(Mutex::new(Connection::open(deckdb)?));
+        self.load_models(&ankon)?;
+        self.load_notes(&ankon, deckname, paths)?;
+        self.load_cards(&ankon)?;
+        if self.cards.is_empty() {
+            bail!("the deck has no cards");
+        }
         self.refresh_template_and_view();
+        Ok(())
+    }
+
+    pub fn card_count(&self) -> usize {
+        self.cards.len()
     }
 
     fn selected_note_id(&self) -> NoteID {
@@ -570,17 +596,17 @@ impl Template {
 
     fn load_cards(&mut self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
         let guard = conn.lock().unwrap();
-        let mut stmt = guard.prepare("SELECT nid, ord FROM cards").unwrap();
+        let mut stmt = guard.prepare("SELECT nid, ord FROM cards")?;
         let foo = stmt.query_map([], |row| {
-            let note_id: NoteID = row.get::<usize, NoteID>(0).unwrap();
-            let template_ord: usize = row.get::<usize, usize>(1).unwrap();
+            let note_id: NoteID = row.get::<usize, NoteID>(0)?;
+            let template_ord: usize = row.get::<usize, usize>(1)?;
             Ok(Kort {
                 note_id,
                 template_ord,
             })
         })?;
         for x in foo {
-            self.cards.push(x.unwrap());
+            self.cards.push(x?);
         }
         Ok(())
     }
@@ -591,13 +617,12 @@ impl Template {
         paths: &SpekiPaths,
     ) -> Result<()> {
         let guard = conn.lock().unwrap();
-        let mut stmt = guard.prepare("SELECT id, mid, flds FROM notes")?;
+        let mut stmt = guard.prepare("SELECT id, mid, flds, tags FROM notes")?;
         let foo = stmt.query_map([], |row| {
-            let id: NoteID = row.get::<usize, NoteID>(0).unwrap();
-            let model_id: ModelID = row.get::<usize, ModelID>(1).unwrap();
+            let id: NoteID = row.get::<usize, NoteID>(0)?;
+            let model_id: ModelID = row.get::<usize, ModelID>(1)?;
             let fields: Vec<CardField> = row
-                .get::<usize, String>(2)
-                .unwrap()
+                .get::<usize, String>(2)?
                 .split('')
                 .map(|x| {
                     let mut text = x.to_string();
@@ -606,25 +631,31 @@ impl Template {
                     CardField { text, audio, image }
                 })
                 .collect();
-            Ok((id, Note { model_id, fields }))
+            // anki keeps them space separated, with a space on either end
+            let tags = parse_tags(&row.get::<usize, String>(3)?);
+            Ok((
+                id,
+                Note {
+                    model_id,
+                    fields,
+                    tags,
+                },
+            ))
         })?;
 
         for x in foo {
-            let y = x.unwrap();
-            let (id, note) = y;
+            let (id, note) = x?;
             self.notes.insert(id, note.clone());
         }
 
         Ok(())
     }
 
-    fn load_models(&mut self, conn: &Arc<Mutex<Connection>>) {
+    fn load_models(&mut self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
         let guard = conn.lock().unwrap();
-        let rawmodel: String = guard
-            .query_row("select models from col", [], |row| row.get(0))
-            .unwrap();
+        let rawmodel: String = guard.query_row("select models from col", [], |row| row.get(0))?;
 
-        let jsonmodels: serde_json::Value = serde_json::from_str(&rawmodel).unwrap();
+        let jsonmodels: serde_json::Value = serde_json::from_str(&rawmodel)?;
         let mut models = Vec::<Model>::new();
         let mut model_ids = Vec::<ModelID>::new();
 
@@ -645,8 +676,8 @@ impl Template {
                             new.push(c);
                         }
                     }
-                    let formatted = new.parse::<ModelID>().unwrap();
-                    formatted
+                    new.parse::<ModelID>()
+                        .with_context(|| format!("invalid note type id: {}", raw))?
                 });
 
                 let mut fieldvec: Vec<String> = Vec::new();
@@ -658,11 +689,11 @@ impl Template {
                             text.remove(0);
                             fieldvec.push(text);
                         } else {
-                            panic!()
+                            bail!("the note types of the deck are malformed");
                         }
                     }
                 } else {
-                    panic!()
+                    bail!("the note types of the deck are malformed");
                 }
 
                 let mut tmplvec: Vec<Temple> = vec![];
@@ -682,24 +713,24 @@ impl Template {
                             temp.afmt = self.fix_format(afmt, &fieldvec);
                             tmplvec.push(temp);
                         } else {
-                            panic!()
+                            bail!("the note types of the deck are malformed");
                         }
                     }
                 } else {
-                    panic!()
+                    bail!("the note types of the deck are malformed");
                 }
                 model.fields = fieldvec;
                 model.templates = tmplvec;
                 models.push(model);
             }
         } else {
-            panic!()
+            bail!("the note types of the deck are malformed");
         }
 
-        assert!(models.len() == model_ids.len());
         for (idx, id) in model_ids.iter().enumerate() {
             self.models.insert(*id, models[idx].clone());
         }
+        Ok(())
     }
 
     fn with_cloze_braces(string: String) -> String {
@@ -721,29 +752,48 @@ impl Template {
         &mut self,
         conn: Arc<Mutex<Connection>>,
         transmitter: std::sync::mpsc::SyncSender<ImportProgress>,
+        cancel: Arc<AtomicBool>,
     ) {
         let cardlen = self.cards.len();
-        let topic = self.topics.get_selected_id().unwrap();
+        let topic = match self.topics.get_selected_id() {
+            Some(topic) => topic,
+            None => {
+                let _ = transmitter.send(ImportProgress {
+                    curr_index: 0,
+                    total: cardlen,
+                    status: ImportStatus::Failed("no topic is selected".to_string()),
+                });
+                return;
+            }
+        };
 
+        // rendering the templates is the slow part, it's done before the database gets locked so
+        // the rest of speki keeps working in the meantime
+        let mut cards = Vec::with_capacity(cardlen);
         for idx in 0..cardlen {
-            //dbg!(idx);
-
-            let front_template = self.get_front_template(idx);
-            let back_template = self.get_back_template(idx);
-            let frontside = self.fill_front_view(front_template, idx);
-            let backside = self.fill_back_view(back_template, idx);
-            let media = self.get_media(idx);
-
-            //    dbg!(afterprepare);
-
+            if cancel.load(Ordering::Relaxed) {
+                let _ = transmitter.send(ImportProgress {
+                    curr_index: idx,
+                    total: cardlen,
+                    status: ImportStatus::Cancelled,
+                });
+                return;
+            }
             if idx % 10 == 0 {
                 let _ = transmitter.try_send(ImportProgress {
                     curr_index: idx,
                     total: cardlen,
+                    status: ImportStatus::Ongoing,
                 });
-            };
+            }
+            let front_template = self.get_front_template(idx);
+            let back_template = self.get_back_template(idx);
+            let frontside = self.fill_front_view(front_template, idx);
+            let backside = self.fill_back_view(back_template, idx);
+            let media = self.get_media(idx);
+            let tags = self.note_from_card_index(idx).tags.clone();
 
-            card::Card::new()
+            let card = card::Card::new()
                 .question(frontside)
                 .answer(backside)
                 .topic(topic)
@@ -751,9 +801,23 @@ impl Template {
                 .backimage(media.backimage)
                 .frontaudio(media.frontaudio)
                 .backaudio(media.backaudio)
-                .cardtype(CardType::Pending)
-                .save_card(&conn);
+                .tags(tags)
+                .cardtype(CardType::Pending);
+            cards.push((card, Some(self.cards[idx].note_id)));
         }
+
+        // all or nothing, so a failed or cancelled import can simply be started over
+        let saved = save_cards(&conn, cards, |_| !cancel.load(Ordering::Relaxed));
+        let status = match saved {
+            Ok(true) => ImportStatus::Done,
+            Ok(false) => ImportStatus::Cancelled,
+            Err(err) => ImportStatus::Failed(err.to_string()),
+        };
+        let _ = transmitter.send(ImportProgress {
+            curr_index: cardlen,
+            total: cardlen,
+            status,
+        });
     }
 
     pub fn render(&mut self, f: &mut tui::Frame<MyType>, area: tui::layout::Rect) {
@@ -879,13 +943,18 @@ impl Template {
             (_, _) => {}
         };
     }
-    pub fn keyhandler(&mut self, conn: &Arc<Mutex<Connection>>, key: MyKey, audio: &Option<Audio>) {
+    pub fn keyhandler(
+        &mut self,
+        conn: &Arc<Mutex<Connection>>,
+        key: MyKey,
+        audio: &Option<Audio>,
+    ) -> Result<()> {
         use MyKey::*;
         use Selected::*;
 
         if let MyKey::Nav(dir) = key {
             self.navigate(dir);
-            return;
+            return Ok(());
         }
 
         match (&self.selected, key) {
@@ -925,8 +994,9 @@ impl Template {
                 self.update_template();
                 self.refresh_views();
             }
-            (Topics, key) => self.topics.keyhandler(key, conn),
+            (Topics, key) => self.topics.keyhandler(key, conn)?,
             (_, _) => {}
         }
+        Ok(())
     }
 }
@@ -13,5 +13,7 @@ pub mod newchild;
 //pub mod optional_bool_filter;
 pub mod progress_bar;
 pub mod radiomenu;
+pub mod search;
+pub mod tag_editor;
 pub mod textinput;
 pub mod topics;
@@ -43,6 +43,11 @@ pub fn mode_status<B>(
         target.pending_qty - current.pending_cards.len() as u16,
         target.pending_qty,
     );
+    let learning = format!(
+        "Learning: {}/{}",
+        target.learn_qty - current.learning.len() as u16,
+        target.learn_qty,
+    );
     let incread = format!(
         "Inc read: {}/{}",
         target.inc_qty - current.active_increads.len() as u16,
@@ -52,12 +57,13 @@ pub fn mode_status<B>(
     let bordercolor = Color::White;
     let style = Style::default().fg(bordercolor);
 
-    let mut modifiers = [Modifier::empty(); 4];
+    let mut modifiers = [Modifier::empty(); 5];
     match mode {
         ReviewMode::Review(_) => modifiers[0] = Modifier::REVERSED,
         ReviewMode::Unfinished(_) => modifiers[1] = Modifier::REVERSED,
         ReviewMode::Pending(_) => modifiers[2] = Modifier::REVERSED,
         ReviewMode::IncRead(_) => modifiers[3] = Modifier::REVERSED,
+        ReviewMode::Learning(..) => modifiers[4] = Modifier::REVERSED,
         ReviewMode::Done => {}
     };
 
@@ -79,6 +85,13 @@ pub fn mode_status<B>(
             Style::default().fg(Color::Cyan).add_modifier(modifiers[2]),
         ),
         Span::from("  "),
+        Span::styled(
+            learning,
+            Style::default()
+                .fg(Color::Magenta)
+                .add_modifier(modifiers[4]),
+        ),
+        Span::from("  "),
         Span::styled(
             incread,
             Style::default().fg(Color::Green).add_modifier(modifiers[3]),
@@ -1,3 +1,4 @@
+use crate::error::Result;
 use crate::utils::card::CardType;
 use crate::utils::sql::fetch::{fetch_question, get_topic_of_card};
 use crate::utils::{aliases::*, card::Card};
@@ -36,51 +37,55 @@ pub struct AddChildWidget {
 }
 
 impl AddChildWidget {
-    pub fn new(conn: &Arc<Mutex<Connection>>, purpose: Purpose) -> Self {
-        let prompt = Self::add_prompt(conn, &purpose);
+    pub fn new(conn: &Arc<Mutex<Connection>>, purpose: Purpose) -> Result<Self> {
+        let prompt = Self::add_prompt(conn, &purpose)?;
         let question = Field::new();
         let answer = Field::new();
         let selection = Selection::Question;
         let status = PopUpStatus::OnGoing;
 
-        AddChildWidget {
+        Ok(AddChildWidget {
             prompt,
             question,
             answer,
             status,
             selection,
             purpose,
-        }
+        })
     }
 
-    fn add_prompt(conn: &Arc<Mutex<Connection>>, purpose: &Purpose) -> Field {
+    fn add_prompt(conn: &Arc<Mutex<Connection>>, purpose: &Purpose) -> Result<Field> {
         let mut prompt = Field::new();
         match purpose {
             Purpose::Source(id) => {
                 prompt.push("Add new sourced card".to_string());
-                let sourcetext = load_inc_text(conn, *id).unwrap();
+                let sourcetext = load_inc_text(conn, *id)?;
                 prompt.push(sourcetext);
             }
             Purpose::Dependency(id) => {
                 prompt.push("Add new dependent of: ".to_string());
-                let ques = fetch_question(conn, *id);
+                let ques = fetch_question(conn, *id)?;
                 prompt.push(ques)
             }
             Purpose::Dependent(id) => {
                 prompt.push("Add new dependency of: ".to_string());
-                let ques = fetch_question(conn, *id);
+                let ques = fetch_question(conn, *id)?;
                 prompt.push(ques)
             }
         }
-        prompt
+        Ok(prompt)
     }
 
-    pub fn keyhandler(&mut self, conn: &Arc<Mutex<Connection>>, key: MyKey) -> PopUpStatus {
+    pub fn keyhandler(
+        &mut self,
+        conn: &Arc<Mutex<Connection>>,
+        key: MyKey,
+    ) -> Result<PopUpStatus> {
         use MyKey::*;
         match key {
             Esc => self.status = PopUpStatus::Finished,
-            Alt('f') => self.submit_card(conn, true),
-            Alt('u') => self.submit_card(conn, false),
+            Alt('f') => self.submit_card(conn, true)?,
+            Alt('u') => self.submit_card(conn, false)?,
             Nav(Direction::Up) => self.selection = Selection::Question,
             Nav(Direction::Down) => self.selection = Selection::Answer,
             key => match self.selection {
@@ -88,14 +93,14 @@ impl AddChildWidget {
                 Selection::Answer => self.answer.keyhandler(key),
             },
         }
-        self.status.clone()
+        Ok(self.status.clone())
     }
 
-    fn submit_card(&mut self, conn: &Arc<Mutex<Connection>>, isfinished: bool) {
+    fn submit_card(&mut self, conn: &Arc<Mutex<Connection>>, isfinished: bool) -> Result<()> {
         let topic = match self.purpose {
-            Purpose::Source(id) => get_topic_of_inc(&conn, id).unwrap(),
-            Purpose::Dependent(id) => get_topic_of_card(&conn, id),
-            Purpose::Dependency(id) => get_topic_of_card(&conn, id),
+            Purpose::Source(id) => get_topic_of_inc(conn, id)?,
+            Purpose::Dependent(id) => get_topic_of_card(conn, id)?,
+            Purpose::Dependency(id) => get_topic_of_card(conn, id)?,
         };
 
         let question = self.question.return_text();
@@ -127,8 +132,9 @@ impl AddChildWidget {
             }
             _ => {}
         }
-        card.save_card(conn);
+        card.save_card(conn)?;
         self.status = PopUpStatus::Finished;
+        Ok(())
     }
 
     pub fn render<B>(&mut self, f: &mut Frame<B>, area: Rect)
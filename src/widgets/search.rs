@@ -0,0 +1,157 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use rusqlite::Connection;
+use tui::{
+    layout::{Constraint, Direction::Vertical, Layout, Rect},
+    style::Style,
+    Frame,
+};
+
+use crate::error::Result;
+use crate::utils::aliases::*;
+use crate::utils::misc::PopUpStatus;
+use crate::utils::sql::fetch::{search_incread, CardQuery};
+use crate::utils::statelist::StatefulList;
+use crate::widgets::message_box::draw_message;
+use crate::widgets::textinput::Field;
+use crate::{MyKey, MyType};
+
+const MAX_HITS: u32 = 25;
+
+/// Where a search result leads to.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Target {
+    Card(CardID),
+    Source(IncID),
+}
+
+#[derive(Clone)]
+pub struct SearchHit {
+    pub target: Target,
+    pub text: String,
+}
+
+impl fmt::Display for SearchHit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.target {
+            Target::Card(_) => "card",
+            Target::Source(_) => "text",
+        };
+        write!(f, "[{}] {}", kind, self.text)
+    }
+}
+
+/// Searches every card and incremental reading text at once, from any tab.
+pub struct SearchWidget {
+    pub searchterm: Field,
+    pub results: StatefulList<SearchHit>,
+    pub status: PopUpStatus,
+    pub chosen: Option<Target>,
+}
+
+impl Default for SearchWidget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SearchWidget {
+    pub fn new() -> Self {
+        SearchWidget {
+            searchterm: Field::new(),
+            results: StatefulList::new(),
+            status: PopUpStatus::OnGoing,
+            chosen: None,
+        }
+    }
+
+    pub fn keyhandler(
+        &mut self,
+        conn: &Arc<Mutex<Connection>>,
+        key: MyKey,
+    ) -> Result<PopUpStatus> {
+        match key {
+            MyKey::Enter => {
+                if let Some(hit) = self.results.clone_selected() {
+                    self.chosen = Some(hit.target);
+                    self.status = PopUpStatus::Finished;
+                }
+            }
+            MyKey::Esc => self.status = PopUpStatus::Finished,
+            MyKey::Down => self.results.next(),
+            MyKey::Up => self.results.previous(),
+            key => {
+                self.searchterm.keyhandler(key);
+                self.update(conn)?;
+            }
+        }
+        Ok(self.status.clone())
+    }
+
+    fn update(&mut self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
+        let text = self.searchterm.return_text();
+        let mut hits = vec![];
+        if !text.trim().is_empty() {
+            // #tag keeps the cards with that tag and -#tag the ones without, texts have no tags
+            let mut query = CardQuery::default();
+            let mut words = vec![];
+            let mut tagged = false;
+            for word in text.split_whitespace() {
+                if let Some(tag) = word.strip_prefix("-#").filter(|tag| !tag.is_empty()) {
+                    query = query.not_tagged(tag);
+                    tagged = true;
+                } else if let Some(tag) = word.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+                    query = query.tagged(tag);
+                    tagged = true;
+                } else {
+                    words.push(word);
+                }
+            }
+            let text = words.join(" ");
+            let cards = query
+                .search(&text)
+                .limit(MAX_HITS)
+                .fetch_carditems(conn)?;
+            hits.extend(cards.into_iter().map(|card| SearchHit {
+                target: Target::Card(card.id),
+                text: card.question,
+            }));
+            let sources = if tagged {
+                vec![]
+            } else {
+                search_incread(conn, &text, MAX_HITS)?
+            };
+            hits.extend(sources.into_iter().map(|source| SearchHit {
+                target: Target::Source(source.id),
+                text: source.text,
+            }));
+        }
+        self.results = StatefulList::with_items(hits);
+        self.results.next();
+        Ok(())
+    }
+
+    pub fn render(&mut self, f: &mut Frame<MyType>, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Vertical)
+            .constraints(
+                [
+                    Constraint::Length(2),
+                    Constraint::Length(3),
+                    Constraint::Min(0),
+                ]
+                .as_ref(),
+            )
+            .split(area);
+
+        draw_message(
+            f,
+            chunks[0],
+            "Search cards and texts. \"quotes\" for exact phrases, enter to go there",
+        );
+        self.searchterm.render(f, chunks[1], false);
+        self.results
+            .render(f, chunks[2], true, "Results", Style::default());
+    }
+}
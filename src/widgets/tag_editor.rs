@@ -0,0 +1,68 @@
+use crate::error::Result;
+use crate::utils::aliases::*;
+use crate::utils::misc::{split_updown, PopUpStatus};
+use crate::utils::sql::tags::{all_tags, get_tags, parse_tags, set_tags};
+use crate::widgets::message_box::draw_message;
+use crate::widgets::textinput::Field;
+use crate::{MyKey, MyType};
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex};
+use tui::{layout::Rect, Frame};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Popup for changing the tags of a card. Enter saves them, escape leaves them as they were.
+pub struct TagEditor {
+    pub id: CardID,
+    pub tags: Field,
+    /// Every tag in use, to pick from.
+    pub known: String,
+}
+
+impl TagEditor {
+    pub fn new(conn: &Arc<Mutex<Connection>>, id: CardID) -> Result<Self> {
+        let mut text = get_tags(conn, id)?.join(" ");
+        if !text.is_empty() {
+            text.push(' ');
+        }
+        let column = text.graphemes(true).count();
+        let known: Vec<String> = all_tags(conn)?
+            .into_iter()
+            .map(|(tag, count)| format!("{} ({})", tag, count))
+            .collect();
+        Ok(TagEditor {
+            id,
+            tags: Field::new_with_text(text, 0, column),
+            known: known.join(", "),
+        })
+    }
+
+    pub fn keyhandler(&mut self, conn: &Arc<Mutex<Connection>>, key: MyKey) -> Result<PopUpStatus> {
+        match key {
+            MyKey::Enter => {
+                set_tags(conn, self.id, &parse_tags(&self.tags.return_text()))?;
+                Ok(PopUpStatus::Finished)
+            }
+            MyKey::Esc => Ok(PopUpStatus::Finished),
+            key => {
+                self.tags.keyhandler(key);
+                Ok(PopUpStatus::OnGoing)
+            }
+        }
+    }
+
+    pub fn render(&mut self, f: &mut Frame<MyType>, area: Rect) {
+        let chunks = split_updown([20, 20, 60], area);
+        draw_message(
+            f,
+            chunks[0],
+            "Tags of this card, separated by spaces. Enter saves them, escape cancels.",
+        );
+        self.tags.render(f, chunks[1], true);
+        let known = if self.known.is_empty() {
+            "No tags yet"
+        } else {
+            &self.known
+        };
+        draw_message(f, chunks[2], known);
+    }
+}
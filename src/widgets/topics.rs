@@ -1,7 +1,8 @@
+use crate::error::Result;
 use crate::utils::aliases::*;
 use crate::utils::sql::delete::delete_topic;
 use crate::utils::sql::fetch::get_topics;
-use crate::utils::sql::update::{update_card_topic, update_topic_parent, update_topic_relpos};
+use crate::utils::sql::update::{update_topic_parent, update_topic_relpos};
 use rusqlite::Connection;
 use tui::layout::Rect;
 use tui::Frame;
@@ -198,73 +199,75 @@ impl TopicList {
         below_index - current_index
     }
 
-    pub fn shift_left(&mut self, conn: &Arc<Mutex<Connection>>, index: u32) {
+    pub fn shift_left(&mut self, conn: &Arc<Mutex<Connection>>, index: u32) -> Result<()> {
         let topic = self.topic_from_index(index);
         let parent = self.parent_from_id(topic.id);
         let uncles = self.uncles_from_id(topic.id);
         let uncle_qty = uncles.len() as u32;
 
-        update_topic_parent(&conn, topic.id, parent.parent).unwrap();
-        update_topic_relpos(&conn, topic.id, parent.relpos).unwrap();
+        update_topic_parent(&conn, topic.id, parent.parent)?;
+        update_topic_relpos(&conn, topic.id, parent.relpos)?;
 
         for i in parent.relpos..uncle_qty {
             let uncle_id = uncles[i as usize];
             let uncle = self.topic_from_id(uncle_id);
-            update_topic_relpos(&conn, uncle_id, uncle.relpos + 1).unwrap();
+            update_topic_relpos(&conn, uncle_id, uncle.relpos + 1)?;
         }
         let siblings = self.siblings_from_id(topic.id);
         let sibling_qty = siblings.len() as u32;
 
         for i in (topic.relpos + 1)..sibling_qty {
             let sibling = self.topic_from_id(siblings[i as usize]);
-            update_topic_relpos(&conn, siblings[i as usize], sibling.relpos - 1).unwrap();
+            update_topic_relpos(&conn, siblings[i as usize], sibling.relpos - 1)?;
         }
+        Ok(())
     }
 
-    pub fn delete_topic(&mut self, conn: &Arc<Mutex<Connection>>, index: u32) {
+    pub fn delete_topic(&mut self, conn: &Arc<Mutex<Connection>>, index: u32) -> Result<()> {
         let topic = self.topic_from_index(index);
 
         for (index, child) in topic.children.iter().enumerate() {
-            update_topic_parent(&conn, *child, topic.parent).unwrap();
-            update_topic_relpos(&conn, *child, topic.relpos + index as u32).unwrap();
+            update_topic_parent(&conn, *child, topic.parent)?;
+            update_topic_relpos(&conn, *child, topic.relpos + index as u32)?;
         }
 
-        delete_topic(&conn, topic.id).unwrap();
-        update_card_topic(&conn, topic.id, topic.parent).unwrap(); // all the cards with the deleted topic
-                                                                   // get assigned to the topic above item
+        delete_topic(conn, topic.id, &topic.children)?; // the cards with the deleted topic get assigned
+                                                                // to the topic above it by the database
 
         let siblings = self.siblings_from_id(topic.id);
         let siblingqty = siblings.len() as u32;
         let kidqty = topic.children.len() as u32;
 
         for i in (topic.relpos + 1)..(siblingqty) {
-            update_topic_relpos(&conn, siblings[(i) as usize], i + kidqty - 1).unwrap();
+            update_topic_relpos(&conn, siblings[(i) as usize], i + kidqty - 1)?;
         }
+        Ok(())
     }
 
-    pub fn shift_right(&mut self, conn: &Arc<Mutex<Connection>>, index: u32) {
+    pub fn shift_right(&mut self, conn: &Arc<Mutex<Connection>>, index: u32) -> Result<()> {
         let topic = self.topic_from_index(index);
         let below = self.topic_from_index(index + 1);
-        update_topic_parent(&conn, topic.id, below.id).unwrap();
-        update_topic_relpos(&conn, topic.id, 0).unwrap();
+        update_topic_parent(&conn, topic.id, below.id)?;
+        update_topic_relpos(&conn, topic.id, 0)?;
 
         for child_id in below.children {
             let child = self.topic_from_id(child_id);
-            update_topic_relpos(&conn, child_id, child.relpos + 1).unwrap();
+            update_topic_relpos(&conn, child_id, child.relpos + 1)?;
         }
         let siblings = self.siblings_from_id(topic.id);
         let sibling_qty = siblings.len() as u32;
 
         for i in (topic.relpos + 1)..sibling_qty {
             let sib = self.topic_from_id(siblings[i as usize]);
-            update_topic_relpos(&conn, sib.id, sib.relpos - 1).unwrap();
+            update_topic_relpos(&conn, sib.id, sib.relpos - 1)?;
         }
+        Ok(())
     }
 
-    pub fn shift_down(&mut self, conn: &Arc<Mutex<Connection>>, index: u32) {
+    pub fn shift_down(&mut self, conn: &Arc<Mutex<Connection>>, index: u32) -> Result<()> {
         let topic = self.topic_from_index(index);
         if self.is_last_sibling(topic.id) {
-            return;
+            return Ok(());
         }
         let siblings = self.siblings_from_id(topic.id);
         let below_sibling = self.sibling_below(topic.id);
@@ -273,20 +276,21 @@ impl TopicList {
         // if topic is not the last relpos, shift its relpos one down and the below it one up
 
         if topic.relpos != sibling_qty - 1 {
-            update_topic_relpos(&conn, topic.id, topic.relpos + 1).unwrap();
-            update_topic_relpos(&conn, below_sibling.id, topic.relpos).unwrap();
-            return;
+            update_topic_relpos(&conn, topic.id, topic.relpos + 1)?;
+            update_topic_relpos(&conn, below_sibling.id, topic.relpos)?;
         }
+        Ok(())
     }
 
-    pub fn shift_up(&mut self, conn: &Arc<Mutex<Connection>>, index: u32) {
+    pub fn shift_up(&mut self, conn: &Arc<Mutex<Connection>>, index: u32) -> Result<()> {
         let topic = self.topic_from_index(index);
         if topic.relpos == 0 {
-            return;
+            return Ok(());
         }
         let sibling_above = self.sibling_above(topic.id);
-        update_topic_relpos(&conn, topic.id, topic.relpos - 1).unwrap();
-        update_topic_relpos(&conn, sibling_above.id, topic.relpos).unwrap();
+        update_topic_relpos(&conn, topic.id, topic.relpos - 1)?;
+        update_topic_relpos(&conn, sibling_above.id, topic.relpos)?;
+        Ok(())
     }
 
     fn dfs(&mut self, id: u32, indices: &mut Vec<u32>) {
@@ -323,10 +327,11 @@ impl TopicList {
         self.items = sorted_topics;
     }
 
-    pub fn reload_topics(&mut self, conn: &Arc<Mutex<Connection>>) {
-        self.items = get_topics(&conn).unwrap();
+    pub fn reload_topics(&mut self, conn: &Arc<Mutex<Connection>>) -> Result<()> {
+        self.items = get_topics(&conn)?;
         self.add_kids();
         self.sort_topics();
+        Ok(())
     }
 
     pub fn next(&mut self) {
@@ -365,7 +370,7 @@ impl TopicList {
         self.state.select(Some(i));
     }
 
-    pub fn keyhandler(&mut self, key: MyKey, conn: &Arc<Mutex<Connection>>) {
+    pub fn keyhandler(&mut self, key: MyKey, conn: &Arc<Mutex<Connection>>) -> Result<()> {
         use MyKey::*;
         match &mut self.writing {
             Some(inner) => match key {
@@ -373,15 +378,15 @@ impl TopicList {
                     inner.name.addchar(c);
                     let id = inner.id;
                     let name = inner.name.return_text();
-                    update_topic_name(&conn, id, name).unwrap();
-                    self.reload_topics(conn);
+                    update_topic_name(&conn, id, name)?;
+                    self.reload_topics(conn)?;
                 }
                 Backspace => {
                     inner.name.backspace();
                     let id = inner.id;
                     let name = inner.name.return_text();
-                    update_topic_name(&conn, id, name).unwrap();
-                    self.reload_topics(conn);
+                    update_topic_name(&conn, id, name)?;
+                    self.reload_topics(conn)?;
                 }
                 Enter => {
                     let id = inner.id;
@@ -398,61 +403,61 @@ impl TopicList {
                 Delete => {
                     let mut index = self.state.selected().unwrap() as u32;
                     if index == 0 {
-                        return;
+                        return Ok(());
                     }
-                    self.delete_topic(conn, index);
+                    self.delete_topic(conn, index)?;
                     if index == self.items.len() as u32 - 1 {
                         index -= 1
                     }
-                    self.reload_topics(conn);
+                    self.reload_topics(conn)?;
                     self.state.select(Some((index) as usize));
                 }
                 Char('h') => {
                     let index = self.state.selected().unwrap() as u32;
                     let topic = self.items[index as usize].clone();
                     if topic.parent == 1 {
-                        return;
+                        return Ok(());
                     }
                     if index == 0 {
-                        return;
+                        return Ok(());
                     }
                     let parent_index = self.index_from_id(topic.parent);
-                    self.shift_left(conn, index);
-                    self.reload_topics(conn);
+                    self.shift_left(conn, index)?;
+                    self.reload_topics(conn)?;
                     self.state.select(Some((parent_index) as usize));
                 }
                 Char('l') => {
                     let index = self.state.selected().unwrap() as u32;
                     if index == (self.items.len() as u32) - 1 {
-                        return;
+                        return Ok(());
                     }
                     if index == 0 {
-                        return;
+                        return Ok(());
                     }
                     let topic = self.topic_from_index(index);
                     if self.is_last_sibling(topic.id) {
-                        return;
+                        return Ok(());
                     }
                     if self.items[index as usize].children.len() > 0 {
-                        return;
+                        return Ok(());
                     }
-                    self.shift_right(conn, index as u32);
-                    self.reload_topics(conn);
+                    self.shift_right(conn, index as u32)?;
+                    self.reload_topics(conn)?;
                     self.state.select(Some((index + 1) as usize));
                 }
                 Char('J') => {
                     let index = self.state.selected().unwrap() as u32;
                     let topic = self.items[index as usize].clone();
-                    self.shift_down(conn, index as u32);
-                    self.reload_topics(conn);
+                    self.shift_down(conn, index as u32)?;
+                    self.reload_topics(conn)?;
                     let new_index = self.index_from_id(topic.id);
                     self.state.select(Some((new_index) as usize));
                 }
                 Char('K') => {
                     let index = self.state.selected().unwrap();
                     let topic = self.items[index as usize].clone();
-                    self.shift_up(conn, index as u32);
-                    self.reload_topics(conn);
+                    self.shift_up(conn, index as u32)?;
+                    self.reload_topics(conn)?;
                     let new_index = self.index_from_id(topic.id);
                     self.state.select(Some(new_index as usize));
                 }
@@ -468,14 +473,15 @@ impl TopicList {
                     let name = String::new();
                     let children = self.items[parent_index].children.clone();
                     let sibling_qty = children.len();
-                    new_topic(conn, name, parent, sibling_qty as u32).unwrap();
+                    new_topic(conn, name, parent, sibling_qty as u32)?;
                     let id = (conn.lock().unwrap().last_insert_rowid()) as u32;
                     self.writing = Some(NewTopic::new(id));
-                    self.reload_topics(conn);
+                    self.reload_topics(conn)?;
                 }
                 _ => {}
             },
         }
+        Ok(())
     }
 
     pub fn render(